@@ -0,0 +1,279 @@
+use anyhow::{Ok, Result};
+use std::str::FromStr;
+
+const SOL_DECIMALS: usize = 9;
+
+/// An amount of SOL parsed exactly into lamports, e.g "1.5", "1.5SOL",
+/// "2500lamports" or "ALL"
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Amount {
+    Lamports(u64),
+    /// Everything available in the wallet
+    All,
+}
+
+impl Amount {
+    /// The exact lamport amount, failing for `ALL` where the command has no
+    /// balance to resolve it against
+    pub fn lamports(&self, command: &str) -> Result<u64> {
+        match self {
+            Amount::Lamports(lamports) => Ok(*lamports),
+            Amount::All => anyhow::bail!("`ALL` is not a valid amount for {}", command),
+        }
+    }
+}
+
+impl FromStr for Amount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let value = s.trim();
+        if value.eq_ignore_ascii_case("all") {
+            return Ok(Amount::All);
+        }
+        if value.starts_with('-') {
+            anyhow::bail!("Amount `{}` cannot be negative", s);
+        }
+        let lower = value.to_lowercase();
+        if let Some(lamports) = lower
+            .strip_suffix("lamports")
+            .or_else(|| lower.strip_suffix("lamport"))
+        {
+            let lamports = lamports.trim();
+            if lamports.is_empty() || !lamports.chars().all(|c| c.is_ascii_digit()) {
+                anyhow::bail!("Invalid lamport amount `{}`, expected a whole number", s);
+            }
+            let lamports = lamports
+                .parse::<u64>()
+                .map_err(|_| anyhow::anyhow!("Amount `{}` is too large", s))?;
+            return Ok(Amount::Lamports(lamports));
+        }
+        let sol = lower.strip_suffix("sol").unwrap_or(&lower).trim();
+        Ok(Amount::Lamports(parse_sol(sol).map_err(|e| {
+            anyhow::anyhow!("Invalid amount `{}`: {}", s, e)
+        })?))
+    }
+}
+
+fn parse_sol(sol: &str) -> Result<u64> {
+    parse_decimal(sol, SOL_DECIMALS, "SOL")
+}
+
+/// Splits a non-negative decimal number into its whole and fraction digits
+fn split_decimal<'a>(value: &'a str, unit: &str) -> Result<(&'a str, &'a str)> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if (whole.is_empty() && fraction.is_empty())
+        || !whole.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        anyhow::bail!("expected a number of {} e.g 1.5", unit);
+    }
+    Ok((whole, fraction))
+}
+
+/// Parses a non-negative decimal number into base units of `decimals` places
+fn parse_decimal(value: &str, decimals: usize, unit: &str) -> Result<u64> {
+    let (whole, fraction) = split_decimal(value, unit)?;
+    if fraction.len() > decimals {
+        anyhow::bail!("{} has at most {} decimal places", unit, decimals);
+    }
+    let whole = if whole.is_empty() {
+        0
+    } else {
+        whole
+            .parse::<u64>()
+            .map_err(|_| anyhow::anyhow!("amount is too large"))?
+    };
+    let fraction = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<width$}", fraction, width = decimals).parse::<u64>()?
+    };
+    10u64
+        .checked_pow(decimals as u32)
+        .and_then(|scale| whole.checked_mul(scale))
+        .and_then(|units| units.checked_add(fraction))
+        .ok_or_else(|| anyhow::anyhow!("amount is too large"))
+}
+
+/// Formats lamports as an exact SOL amount without trailing zeros
+pub fn format_sol(lamports: u64) -> String {
+    format_decimal(lamports, SOL_DECIMALS)
+}
+
+/// Formats base units of a token with `decimals` places without trailing zeros
+pub fn format_token_amount(amount: u64, decimals: u8) -> String {
+    format_decimal(amount, decimals as usize)
+}
+
+fn format_decimal(units: u64, decimals: usize) -> String {
+    let scale = 10u128.pow(decimals as u32);
+    let whole = units as u128 / scale;
+    let fraction = units as u128 % scale;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:0>width$}", fraction, width = decimals);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+/// An amount of an SPL token e.g "1.5" or "ALL", only resolved into base
+/// units once the mint's decimals are known
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenAmount {
+    Decimal(String),
+    /// The whole balance of the token account
+    All,
+}
+
+impl TokenAmount {
+    /// The amount in base units, `balance` is what `ALL` resolves to
+    pub fn base_units(&self, decimals: u8, balance: u64) -> Result<u64> {
+        match self {
+            TokenAmount::Decimal(value) => parse_decimal(value, decimals as usize, "this token")
+                .map_err(|e| anyhow::anyhow!("Invalid amount `{}`: {}", value, e)),
+            TokenAmount::All => Ok(balance),
+        }
+    }
+
+    /// The amount in base units rounded down to `decimals` places, for
+    /// thresholds shared by mints of different decimals. Amounts too large
+    /// for a `u64` and `ALL` saturate.
+    pub fn base_units_floor(&self, decimals: u8) -> u64 {
+        match self {
+            TokenAmount::Decimal(value) => {
+                let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+                let whole = if whole.is_empty() { "0" } else { whole };
+                let fraction = &fraction[..fraction.len().min(decimals as usize)];
+                parse_decimal(
+                    &format!("{}.{}", whole, fraction),
+                    decimals as usize,
+                    "tokens",
+                )
+                .unwrap_or(u64::MAX)
+            }
+            TokenAmount::All => u64::MAX,
+        }
+    }
+}
+
+impl FromStr for TokenAmount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let value = s.trim();
+        if value.eq_ignore_ascii_case("all") {
+            return Ok(TokenAmount::All);
+        }
+        if value.starts_with('-') {
+            anyhow::bail!("Amount `{}` cannot be negative", s);
+        }
+        // The decimal places are checked once the mint is known
+        split_decimal(value, "tokens")
+            .map_err(|e| anyhow::anyhow!("Invalid amount `{}`: {}", s, e))?;
+        Ok(TokenAmount::Decimal(value.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lamports(s: &str) -> u64 {
+        s.parse::<Amount>().unwrap().lamports("test").unwrap()
+    }
+
+    #[test]
+    fn parses_sol_exactly() {
+        assert_eq!(lamports("1"), 1_000_000_000);
+        assert_eq!(lamports("1.5"), 1_500_000_000);
+        assert_eq!(lamports(".5"), 500_000_000);
+        assert_eq!(lamports("2."), 2_000_000_000);
+        assert_eq!(lamports("0.000000001"), 1);
+        // Parsed digit by digit, an f64 would round these
+        assert_eq!(lamports("0.3"), 300_000_000);
+        assert_eq!(lamports("1.000000009"), 1_000_000_009);
+        assert_eq!(lamports(" 1.5SOL "), 1_500_000_000);
+        assert_eq!(lamports("1.5 sol"), 1_500_000_000);
+    }
+
+    #[test]
+    fn parses_lamports() {
+        assert_eq!(lamports("2500lamports"), 2500);
+        assert_eq!(lamports("1 lamport"), 1);
+        assert_eq!(lamports("18446744073709551615lamports"), u64::MAX);
+        assert!("1.5lamports".parse::<Amount>().is_err());
+        assert!("lamports".parse::<Amount>().is_err());
+        assert!("18446744073709551616lamports".parse::<Amount>().is_err());
+    }
+
+    #[test]
+    fn parses_all() {
+        assert_eq!("ALL".parse::<Amount>().unwrap(), Amount::All);
+        assert_eq!("all".parse::<Amount>().unwrap(), Amount::All);
+        assert!(Amount::All.lamports("airdrop").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_amounts() {
+        for value in ["", ".", "-1", "1e9", "1,5", "1.2.3", "abc", "0x10", "+1"] {
+            assert!(value.parse::<Amount>().is_err(), "{} should fail", value);
+        }
+    }
+
+    #[test]
+    fn rejects_too_many_decimals() {
+        let err = "0.0000000001".parse::<Amount>().unwrap_err();
+        assert!(err.to_string().contains("at most 9 decimal places"));
+        assert!(parse_decimal("1.123", 2, "tokens").is_err());
+        assert_eq!(parse_decimal("1.12", 2, "tokens").unwrap(), 112);
+        assert_eq!(parse_decimal("7", 0, "tokens").unwrap(), 7);
+        assert!(parse_decimal("7.1", 0, "tokens").is_err());
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        // u64::MAX lamports is 18446744073.709551615 SOL
+        assert_eq!(lamports("18446744073.709551615"), u64::MAX);
+        assert!("18446744073.709551616".parse::<Amount>().is_err());
+        assert!("18446744074".parse::<Amount>().is_err());
+        assert!("99999999999999999999".parse::<Amount>().is_err());
+        assert_eq!(
+            parse_decimal("18446744073709551615", 0, "tokens").unwrap(),
+            u64::MAX
+        );
+        assert!(parse_decimal("1", 20, "tokens").is_err());
+    }
+
+    #[test]
+    fn formats_without_trailing_zeros() {
+        assert_eq!(format_sol(0), "0");
+        assert_eq!(format_sol(1), "0.000000001");
+        assert_eq!(format_sol(1_500_000_000), "1.5");
+        assert_eq!(format_sol(u64::MAX), "18446744073.709551615");
+        assert_eq!(format_token_amount(123, 2), "1.23");
+        assert_eq!(format_token_amount(100, 0), "100");
+    }
+
+    #[test]
+    fn token_amount_resolves_with_mint_decimals() {
+        let amount: TokenAmount = "1.25".parse().unwrap();
+        assert_eq!(amount.base_units(2, 0).unwrap(), 125);
+        assert_eq!(amount.base_units(6, 0).unwrap(), 1_250_000);
+        assert!(amount.base_units(1, 0).is_err());
+        assert_eq!(TokenAmount::All.base_units(6, 42).unwrap(), 42);
+        assert!("-1".parse::<TokenAmount>().is_err());
+        assert!("1.5tokens".parse::<TokenAmount>().is_err());
+    }
+
+    #[test]
+    fn token_amount_floor_rounds_down_and_saturates() {
+        let amount: TokenAmount = "1.259".parse().unwrap();
+        assert_eq!(amount.base_units_floor(2), 125);
+        assert_eq!(amount.base_units_floor(0), 1);
+        assert_eq!(amount.base_units_floor(4), 12_590);
+        let huge: TokenAmount = "99999999999999999999".parse().unwrap();
+        assert_eq!(huge.base_units_floor(0), u64::MAX);
+        assert_eq!(TokenAmount::All.base_units_floor(9), u64::MAX);
+    }
+}
@@ -1,31 +1,187 @@
+use crate::amount::{format_sol, Amount};
+use crate::batch::TransferBatchArgs;
+use crate::cleanup::CleanupArgs;
+use crate::config::{ConfigArgs, Settings};
+use crate::grind::GrindArgs;
+use crate::history::HistoryArgs;
+use crate::keystore::{write_encrypted_keypair, write_plain_keypair, KeystoreArgs, SecretFormat};
+use crate::memo::{memo_instruction, parse_memo};
+use crate::mnemonic::{
+    generate_mnemonic, keypair_from_mnemonic, parse_derivation_path, prompt_new_passphrase,
+    read_secret, DEFAULT_DERIVATION_PATH,
+};
+use crate::output::{
+    print_output, print_outputs, AirdropRequested, KeypairGenerated, KeypairRecovered,
+    OutputFormat, TokenBalance, TransferCompleted, TransferSimulation, WalletBalance,
+};
+use crate::policy::MainnetGuard;
+use crate::priority_fee::PriorityFeeArgs;
+use crate::recipient::RecipientCheckArgs;
+use crate::sender::{confirm_signature, did_not_land, new_progress_bar, send_and_confirm_recorded};
+use crate::signer::SignerSource;
+use crate::sweep::{rent_exempt_reserve, sweepable_lamports, SweepArgs};
+use crate::token::{get_token_accounts, program_name, TokenArgs};
+use crate::transaction::TxArgs;
+use crate::wsol::{UnwrapArgs, WrapArgs};
 use anyhow::{Context, Ok, Result};
-use clap::{Args, Parser, Subcommand, ValueEnum};
-use colored::*;
-use indicatif::{ProgressBar, ProgressStyle};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Args, Parser, Subcommand};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::client_error::reqwest::{
+    self,
+    header::{HeaderName, HeaderValue},
+};
 use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::RpcClientConfig;
+use solana_client::rpc_config::{
+    RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+};
 use solana_program::system_instruction;
+use solana_rpc_client::http_sender::HttpSender;
+use solana_sdk::commitment_config::CommitmentLevel;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
 use solana_sdk::native_token::LAMPORTS_PER_SOL;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::{read_keypair_file, write_keypair_file};
+use solana_sdk::signature::Keypair;
 use solana_sdk::signer::Signer;
 use solana_sdk::transaction::Transaction;
-use solana_sdk::{commitment_config::CommitmentConfig, signature::Keypair};
 use std::str::FromStr;
 use std::time::Duration;
 
-#[derive(ValueEnum, Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, PartialEq)]
 pub enum Network {
     #[default]
     Devnet,
+    Testnet,
     Mainnet,
     Localnet,
+    Custom(String),
+}
+
+impl Network {
+    pub fn url(&self) -> &str {
+        match self {
+            Network::Devnet => "https://api.devnet.solana.com",
+            Network::Testnet => "https://api.testnet.solana.com",
+            Network::Mainnet => "https://api.mainnet-beta.solana.com",
+            Network::Localnet => "http://localhost:8899",
+            Network::Custom(url) => url,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "devnet" | "d" => Ok(Network::Devnet),
+            "testnet" | "t" => Ok(Network::Testnet),
+            "mainnet" | "mainnet-beta" | "m" => Ok(Network::Mainnet),
+            "localnet" | "localhost" | "l" => Ok(Network::Localnet),
+            _ if s.starts_with("http://") || s.starts_with("https://") => {
+                Ok(Network::Custom(s.to_string()))
+            }
+            _ => anyhow::bail!(
+                "Unknown network `{}`, expected localnet, devnet, testnet, mainnet or an RPC URL",
+                s
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Network::Devnet => write!(f, "devnet"),
+            Network::Testnet => write!(f, "testnet"),
+            Network::Mainnet => write!(f, "mainnet"),
+            Network::Localnet => write!(f, "localnet"),
+            Network::Custom(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ClusterArgs {
+    /// Network to connect to e.g localnet,devnet,testnet,mainnet or an RPC URL
+    #[arg(short = 'n', long, global = true)]
+    pub network: Option<Network>,
+    /// Custom RPC URL, overrides `--network`
+    #[arg(short = 'u', long, global = true)]
+    pub url: Option<String>,
+    /// Extra HTTP header sent with every RPC request e.g "Authorization: Bearer <token>"
+    #[arg(short = 'H', long = "header", global = true)]
+    pub headers: Vec<String>,
+    /// Commitment level used for RPC requests e.g processed,confirmed,finalized
+    #[arg(long, global = true)]
+    pub commitment: Option<CommitmentLevel>,
 }
 
 #[derive(Args, Clone, Debug)]
 pub struct GenerateArgs {
-    /// file where the generated keypair should be saved
+    /// file where the generated keypair should be saved, defaults to the
+    /// keypair in your config profile
     #[arg(short = 'o', long)]
     pub output_file: Option<std::path::PathBuf>,
+    /// Don't save the keypair, only print it, requires `--show-secret`
+    #[arg(long, conflicts_with_all = ["output_file", "encrypt"], requires = "show_secret")]
+    pub no_outfile: bool,
+    /// Overwrite the output file if it already exists
+    #[arg(long)]
+    pub force: bool,
+    /// Print the secret key, which is never shown otherwise
+    #[arg(long)]
+    pub show_secret: bool,
+    /// Format of the secret key printed by `--show-secret`
+    #[arg(long, value_enum, default_value_t = SecretFormat::JsonArray, requires = "show_secret")]
+    pub format: SecretFormat,
+    /// Derive the keypair from a new BIP39 seed phrase, which is printed so it
+    /// can be written down, requires `--show-secret`
+    #[arg(short = 'm', long, requires = "show_secret")]
+    pub mnemonic: bool,
+    /// Number of words in the seed phrase
+    #[arg(
+        long,
+        default_value = "12",
+        value_parser = PossibleValuesParser::new(["12", "24"]).map(|w| w.parse::<usize>().unwrap()),
+        requires = "mnemonic"
+    )]
+    pub words: usize,
+    /// Protect the seed phrase with a BIP39 passphrase, prompted for interactively
+    #[arg(long, requires = "mnemonic")]
+    pub passphrase: bool,
+    /// Derivation path, either a full path or the `<account>/<change>` shorthand
+    #[arg(long, default_value = DEFAULT_DERIVATION_PATH, requires = "mnemonic")]
+    pub derivation_path: String,
+    /// Encrypt the keypair file with a passphrase, prompted for interactively
+    /// or read from SOL_DASH_KEYPAIR_PASSPHRASE(_FILE)
+    #[arg(long)]
+    pub encrypt: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct RecoverArgs {
+    /// file where the recovered keypair should be saved
+    #[arg(short = 'o', long)]
+    pub output_file: std::path::PathBuf,
+    /// Derivation path, either a full path or the `<account>/<change>` shorthand
+    #[arg(long, default_value = DEFAULT_DERIVATION_PATH)]
+    pub derivation_path: String,
+    /// Derive the keypair like `solana-keygen` does without a derivation path
+    #[arg(long, conflicts_with = "derivation_path")]
+    pub legacy: bool,
+    /// The seed phrase is protected by a BIP39 passphrase, prompted for interactively
+    #[arg(long)]
+    pub passphrase: bool,
+    /// Overwrite the output file if it already exists
+    #[arg(long)]
+    pub force: bool,
+    /// Encrypt the keypair file with a passphrase, prompted for interactively
+    /// or read from SOL_DASH_KEYPAIR_PASSPHRASE(_FILE)
+    #[arg(long)]
+    pub encrypt: bool,
 }
 
 #[derive(Args, Clone, Debug)]
@@ -33,11 +189,15 @@ pub struct WalletArgs {
     /// The public key of the wallet
     #[arg(short = 'a', long)]
     pub address: Option<String>,
-    /// The path to the keypair file
+    /// The keypair file or signer URI e.g stdin:, prompt: or env:VAR
     #[arg(short = 'k', long)]
-    pub keypair: Option<std::path::PathBuf>,
-    #[arg(short = 'n', long, default_value_t, value_enum)]
-    pub network: Network,
+    pub keypair: Option<SignerSource>,
+    /// Also list the wallet's SPL Token and Token-2022 accounts
+    #[arg(long)]
+    pub tokens: bool,
+    /// Only list the token accounts of this mint
+    #[arg(long, requires = "tokens")]
+    pub mint: Option<String>,
 }
 
 #[derive(Args, Clone, Debug)]
@@ -45,261 +205,720 @@ pub struct AirdropArgs {
     /// The public key of the wallet
     #[arg(short = 'a', long)]
     pub address: Option<String>,
-    /// The path to the keypair file
+    /// The keypair file or signer URI e.g stdin:, prompt: or env:VAR
     #[arg(short = 'k', long)]
-    pub keypair: Option<std::path::PathBuf>,
-    #[arg(short = 'n', long, default_value_t, value_enum)]
-    pub network: Network,
-    #[arg(short = 'v', long)]
-    pub value: u64,
+    pub keypair: Option<SignerSource>,
+    /// Amount to request e.g 1.5, 1.5SOL or 2500lamports
+    #[arg(short = 'v', long, allow_negative_numbers = true)]
+    pub value: Amount,
+    /// Wait for the airdrop to be confirmed and report the new balance
+    #[arg(short = 'w', long)]
+    pub wait: bool,
+    /// Largest amount the faucet hands out per request, larger amounts are
+    /// split into several requests. Defaults to the faucet cap of devnet and
+    /// testnet, and no cap elsewhere
+    #[arg(long)]
+    pub max_per_request: Option<Amount>,
+    /// How many times a rate limited request is retried, at most 20
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(0..=20))]
+    pub max_retries: u32,
 }
 
 #[derive(Args, Clone, Debug)]
 pub struct TransferArgs {
-    /// The path to the keypair file for the wallet where you want to transfer
-    /// from
+    /// The keypair file or signer URI for the wallet where you want to
+    /// transfer from, defaults to the keypair in your config profile
     #[arg(short, long)]
-    pub from: std::path::PathBuf,
+    pub from: Option<SignerSource>,
     /// The wallet address of the wallet where you want to transfer to
     #[arg(short, long)]
     pub to: String,
-    #[arg(short = 'n', long, default_value_t, value_enum)]
-    pub network: Network,
-    #[arg(short = 'v', long)]
-    pub value: f64,
+    /// Amount to transfer e.g 1.5, 1.5SOL, 2500lamports or ALL
+    #[arg(short = 'v', long, allow_negative_numbers = true)]
+    pub value: Amount,
+    /// With `--value ALL`, leave the rent-exempt minimum in the wallet
+    /// instead of emptying it
+    #[arg(long)]
+    pub keep_rent_exempt: bool,
+    /// Text attached to the transfer with the SPL Memo program e.g an
+    /// invoice number
+    #[arg(long, value_parser = parse_memo)]
+    pub memo: Option<String>,
+    /// Build, sign and simulate the transaction without broadcasting it
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Send on mainnet without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+    #[command(flatten)]
+    pub recipient_checks: RecipientCheckArgs,
+    #[command(flatten)]
+    pub priority_fee: PriorityFeeArgs,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     // generate keypair and optionally save it to a file
     Generate(GenerateArgs),
+    // search for a keypair with a vanity address
+    Grind(GrindArgs),
+    // recover a keypair from a seed phrase
+    Recover(RecoverArgs),
+    // encrypt a keypair file with a passphrase
+    Encrypt(KeystoreArgs),
+    // decrypt an encrypted keypair file
+    Decrypt(KeystoreArgs),
     // check wallet balance
     Balance(WalletArgs),
+    // list the past transactions of a wallet
+    History(HistoryArgs),
+    // inspect a transaction
+    Tx(TxArgs),
     // request airdrop
     Airdrop(AirdropArgs),
     // transfer sol
     Transfer(TransferArgs),
+    // transfer sol to every recipient in a payout file
+    TransferBatch(TransferBatchArgs),
+    // transfer the whole balance of one or more wallets to an address
+    Sweep(SweepArgs),
+    // manage and transfer spl tokens
+    Token(TokenArgs),
+    // wrap sol into wsol
+    Wrap(WrapArgs),
+    // unwrap wsol back into sol
+    Unwrap(UnwrapArgs),
+    // close empty token accounts to reclaim their rent
+    Cleanup(CleanupArgs),
+    // manage config profiles
+    Config(ConfigArgs),
 }
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)] // Read from `Cargo.toml`
 #[command(propagate_version = true)]
 pub struct Cli {
+    /// Path to the sol-dash config file, defaults to ~/.config/sol-dash/config.toml
+    #[arg(long, global = true)]
+    pub config: Option<std::path::PathBuf>,
+    /// Config profile to use instead of the active one
+    #[arg(short = 'p', long, global = true)]
+    pub profile: Option<String>,
+    #[command(flatten)]
+    pub cluster: ClusterArgs,
+    /// Output format, defaults to the output format in your config profile or human
+    #[arg(long, global = true, value_enum)]
+    pub output: Option<OutputFormat>,
+    /// Disable colored output, also honored through the NO_COLOR environment variable
+    #[arg(long, global = true)]
+    pub no_color: bool,
     #[command(subcommand)]
     pub command: Commands,
 }
 
 impl GenerateArgs {
-    pub fn generate_keypair(&self) -> Result<()> {
-        let keypair = Keypair::new();
-        match &self.output_file {
-            Some(output_file) => {
-                write_keypair_file(&keypair, output_file).map_err(|e| {
-                    anyhow::anyhow!(
-                        "Failed to write keypair to file `{}`: {}",
-                        output_file.display(),
-                        e
-                    )
-                })?;
-                println!(
-                    "Keypair saved to {}",
-                    format!("{}", output_file.display()).green().bold()
-                );
-            }
-            None => {
-                println!("{}", "No output file specified".yellow().bold());
-            }
+    pub fn generate_keypair(&self, settings: &Settings) -> Result<()> {
+        let output_file = if self.no_outfile {
+            None
+        } else {
+            let default_file = settings.keypair.as_ref().and_then(|k| k.path());
+            Some(self.output_file.clone().or_else(|| default_file.map(|p| p.to_path_buf())).context(
+                "No output file provided, pass `--output-file` or set a default keypair with `sol-dash config set keypair <path>`",
+            )?)
+        };
+        // Refuse before anything is prompted for
+        if let Some(output_file) = &output_file {
+            check_overwrite(output_file, self.force)?;
         }
-        println!(
-            "Wallet address: {}",
-            format!("{}", &keypair.pubkey()).blue().bold()
-        );
-        println!("Keypair:\n{:?}", &keypair.to_bytes());
-        println!(
-            "{}",
-            "DO NOT SHARE THIS KEYPAIR OR THE KEYPAIR FILE WITH ANYONE"
-                .red()
-                .bold()
-        );
+        let (keypair, mnemonic) = if self.mnemonic {
+            let derivation_path = parse_derivation_path(&self.derivation_path)?;
+            let mnemonic = generate_mnemonic(self.words)?;
+            let passphrase = if self.passphrase {
+                prompt_new_passphrase()?
+            } else {
+                String::new()
+            };
+            let keypair =
+                keypair_from_mnemonic(&mnemonic.to_string(), &passphrase, Some(derivation_path))?;
+            (keypair, Some(mnemonic.to_string()))
+        } else {
+            (Keypair::new(), None)
+        };
+        if let Some(output_file) = &output_file {
+            write_json_keypair_file(&keypair, output_file, self.encrypt, self.force)?;
+        }
+        let output = KeypairGenerated {
+            address: keypair.pubkey().to_string(),
+            output_file: output_file.map(|path| path.display().to_string()),
+            secret_key: self.show_secret.then(|| self.format.export(&keypair)),
+            derivation_path: mnemonic.as_ref().map(|_| self.derivation_path.clone()),
+            mnemonic,
+        };
+        print_output(&output, settings.output)?;
         Ok(())
     }
 }
 
-fn get_rpc_client(network: &Network) -> RpcClient {
-    match network {
-        Network::Devnet => RpcClient::new_with_commitment(
-            "https://api.devnet.solana.com".to_string(),
-            CommitmentConfig::finalized(),
-        ),
-        Network::Mainnet => RpcClient::new_with_commitment(
-            "https://api.mainnet-beta.solana.com".to_string(),
-            CommitmentConfig::finalized(),
-        ),
-        Network::Localnet => RpcClient::new_with_commitment(
-            "http://localhost:8899".to_string(),
-            CommitmentConfig::finalized(),
-        ),
+impl RecoverArgs {
+    pub fn recover_keypair(&self, settings: &Settings) -> Result<()> {
+        let derivation_path = if self.legacy {
+            None
+        } else {
+            Some(parse_derivation_path(&self.derivation_path)?)
+        };
+        check_overwrite(&self.output_file, self.force)?;
+        let phrase = read_secret("Seed phrase: ")?;
+        let passphrase = if self.passphrase {
+            read_secret("BIP39 passphrase: ")?
+        } else {
+            String::new()
+        };
+        let keypair = keypair_from_mnemonic(phrase.trim(), &passphrase, derivation_path)?;
+        write_json_keypair_file(&keypair, &self.output_file, self.encrypt, self.force)?;
+        let output = KeypairRecovered {
+            address: keypair.pubkey().to_string(),
+            output_file: self.output_file.display().to_string(),
+            derivation_path: (!self.legacy).then(|| self.derivation_path.clone()),
+        };
+        print_output(&output, settings.output)?;
+        Ok(())
     }
 }
 
-fn read_json_keypair_file(file_path: &std::path::PathBuf) -> Result<Keypair> {
-    let keypair = read_keypair_file(file_path).map_err(|e| {
-        anyhow::anyhow!(
-            "Failed to read keypair from file `{}`: {}",
-            file_path.display(),
-            e
-        )
-    })?;
-    Ok(keypair)
+pub fn get_rpc_client(settings: &Settings) -> Result<RpcClient> {
+    let mut headers = HttpSender::default_headers();
+    for header in &settings.headers {
+        let (name, value) = header
+            .split_once(':')
+            .with_context(|| format!("Invalid header `{}`, expected `Name: value`", header))?;
+        let name = HeaderName::from_str(name.trim())
+            .with_context(|| format!("Invalid header name in `{}`", header))?;
+        let value = HeaderValue::from_str(value.trim())
+            .with_context(|| format!("Invalid header value in `{}`", header))?;
+        headers.insert(name, value);
+    }
+    let client = reqwest::Client::builder()
+        .default_headers(headers)
+        .timeout(Duration::from_secs(30))
+        .build()
+        .context("Failed to build the RPC HTTP client")?;
+    Ok(RpcClient::new_sender(
+        HttpSender::new_with_client(settings.network.url(), client),
+        RpcClientConfig::with_commitment(settings.commitment),
+    ))
+}
+
+pub fn check_overwrite(file_path: &std::path::Path, force: bool) -> Result<()> {
+    if file_path.exists() && !force {
+        anyhow::bail!(
+            "`{}` already exists, pass `--force` to overwrite it",
+            file_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Writes the keypair with owner-only permissions, encrypted after asking
+/// for a passphrase when `encrypt` is set
+fn write_json_keypair_file(
+    keypair: &Keypair,
+    file_path: &std::path::Path,
+    encrypt: bool,
+    force: bool,
+) -> Result<()> {
+    check_overwrite(file_path, force)?;
+    if encrypt {
+        write_encrypted_keypair(keypair, file_path)
+    } else {
+        write_plain_keypair(keypair, file_path)
+    }
 }
 
 impl WalletArgs {
-    pub async fn get_balance_handler(&self) -> Result<()> {
-        match self.network {
-            Network::Devnet | Network::Localnet => {
-                let rpc_client = get_rpc_client(&self.network);
-                self.get_wallet_balance(rpc_client).await?;
-            }
-            Network::Mainnet => {
-                let rpc_client = get_rpc_client(&self.network);
-                self.get_wallet_balance(rpc_client).await?;
-            }
-        }
+    pub async fn get_balance_handler(&self, settings: &Settings) -> Result<()> {
+        let rpc_client = get_rpc_client(settings)?;
+        self.get_wallet_balance(rpc_client, settings).await?;
         Ok(())
     }
 
-    pub async fn get_wallet_balance(&self, rpc_client: RpcClient) -> Result<()> {
-        if self.address.is_none() && self.keypair.is_none() {
+    pub async fn get_wallet_balance(
+        &self,
+        rpc_client: RpcClient,
+        settings: &Settings,
+    ) -> Result<()> {
+        let keypair = match (&self.address, &self.keypair) {
+            (None, None) => settings.keypair.clone(),
+            _ => self.keypair.clone(),
+        };
+        if self.address.is_none() && keypair.is_none() {
             anyhow::bail!("Either `address` or `keypair` must be provided.");
         }
+        let mint = self
+            .mint
+            .as_deref()
+            .map(|mint| {
+                Pubkey::from_str(mint).with_context(|| format!("Invalid mint address: {}", mint))
+            })
+            .transpose()?;
+        let mut wallets = Vec::new();
         if let Some(address) = &self.address {
             let pubkey = Pubkey::from_str(address)
                 .with_context(|| format!("Invalid public key address: {}", address))?;
-            let balance = rpc_client.get_balance(&pubkey).await?;
-            println!(
-                "Your SOL balance is: {}",
-                format!("{}", balance as f64 / LAMPORTS_PER_SOL as f64)
-                    .green()
-                    .bold()
-            );
+            wallets.push(pubkey);
         }
-        if let Some(keypair_path) = &self.keypair {
-            let keypair = read_json_keypair_file(keypair_path)?;
-            let balance = rpc_client.get_balance(&keypair.pubkey()).await?;
-            println!(
-                "Your SOL balance is: {}",
-                format!("{}", balance as f64 / LAMPORTS_PER_SOL as f64)
-                    .green()
-                    .bold()
-            );
+        if let Some(signer) = &keypair {
+            wallets.push(signer.keypair()?.pubkey());
+        }
+        let mut balances = Vec::new();
+        for wallet in wallets {
+            let balance = rpc_client.get_balance(&wallet).await?;
+            let mut wallet_balance = WalletBalance::new(wallet.to_string(), balance);
+            if self.tokens {
+                wallet_balance.tokens =
+                    Some(get_token_balances(&rpc_client, &wallet, mint.as_ref()).await?);
+            }
+            balances.push(wallet_balance);
         }
+        print_outputs(balances, settings.output)?;
         Ok(())
     }
 }
 
+async fn get_token_balances(
+    rpc_client: &RpcClient,
+    owner: &Pubkey,
+    mint: Option<&Pubkey>,
+) -> Result<Vec<TokenBalance>> {
+    let accounts = get_token_accounts(rpc_client, owner, mint).await?;
+    Ok(accounts
+        .into_iter()
+        .map(|account| TokenBalance {
+            account: account.address.to_string(),
+            program: program_name(&account.program_id).to_string(),
+            frozen: account.is_frozen(),
+            amount: account.info.token_amount.amount,
+            decimals: account.info.token_amount.decimals,
+            ui_amount: account.info.token_amount.ui_amount_string,
+            delegated_amount: account
+                .info
+                .delegated_amount
+                .map(|amount| amount.ui_amount_string),
+            delegate: account.info.delegate,
+            mint: account.info.mint,
+        })
+        .collect())
+}
+
 impl AirdropArgs {
-    pub async fn request_airdrop_handler(&self) -> Result<()> {
-        match self.network {
-            Network::Devnet | Network::Localnet => {
-                let rpc_client = get_rpc_client(&self.network);
-                self.request_airdrop(rpc_client).await?;
+    pub async fn request_airdrop_handler(&self, settings: &Settings) -> Result<()> {
+        match settings.network {
+            Network::Devnet | Network::Testnet | Network::Localnet | Network::Custom(_) => {
+                let rpc_client = get_rpc_client(settings)?;
+                self.request_airdrop(rpc_client, settings).await?;
             }
             Network::Mainnet => {
                 anyhow::bail!(
-                    "You can only request for an airdrop on devnet or localnet at the moment"
+                    "You can only request for an airdrop on devnet, testnet or localnet at the moment"
                 );
             }
         }
         Ok(())
     }
 
-    pub async fn request_airdrop(&self, rpc_client: RpcClient) -> Result<()> {
-        if self.address.is_none() && self.keypair.is_none() {
+    pub async fn request_airdrop(&self, rpc_client: RpcClient, settings: &Settings) -> Result<()> {
+        let keypair = match (&self.address, &self.keypair) {
+            (None, None) => settings.keypair.clone(),
+            _ => self.keypair.clone(),
+        };
+        if self.address.is_none() && keypair.is_none() {
             anyhow::bail!("Either `address` or `keypair` must be provided.");
         }
+        let lamports = self.value.lamports("an airdrop")?;
+        let max_per_request = match self.max_per_request {
+            Some(max_per_request) => Some(max_per_request.lamports("--max-per-request")?),
+            None => faucet_cap(&settings.network),
+        };
+        let chunks = split_airdrop(lamports, max_per_request)?;
+        let mut recipients = Vec::new();
         if let Some(address) = &self.address {
             let pubkey = Pubkey::from_str(address)
                 .with_context(|| format!("Invalid public key address: {}", address))?;
-            let signature = rpc_client
-                .request_airdrop(&pubkey, self.value * LAMPORTS_PER_SOL)
-                .await?;
-            println!(
-                "Airdrop requested successfully, signature: {}",
-                format!("{}", &signature).yellow().bold()
-            );
+            recipients.push(pubkey);
         }
-        if let Some(keypair_path) = &self.keypair {
-            let keypair = read_json_keypair_file(keypair_path)?;
-            let signature = rpc_client
-                .request_airdrop(&keypair.pubkey(), self.value * LAMPORTS_PER_SOL)
-                .await?;
-            println!(
-                "Airdrop requested successfully, signature: {}",
-                format!("{}", &signature).yellow().bold()
-            );
+        if let Some(signer) = &keypair {
+            recipients.push(signer.keypair()?.pubkey());
         }
+        let mut airdrops = Vec::new();
+        for pubkey in recipients {
+            for chunk in &chunks {
+                airdrops.push(
+                    self.request_chunk(&rpc_client, &pubkey, *chunk, settings)
+                        .await?,
+                );
+            }
+        }
+        print_outputs(airdrops, settings.output)?;
         Ok(())
     }
+
+    /// Requests a single airdrop, backing off and retrying while the faucet
+    /// is rate limiting us
+    async fn request_chunk(
+        &self,
+        rpc_client: &RpcClient,
+        pubkey: &Pubkey,
+        lamports: u64,
+        settings: &Settings,
+    ) -> Result<AirdropRequested> {
+        let mut attempt = 0;
+        let signature = loop {
+            match rpc_client.request_airdrop(pubkey, lamports).await {
+                Result::Ok(signature) => break signature,
+                Err(err) if attempt < self.max_retries && is_rate_limited(&err.to_string()) => {
+                    attempt += 1;
+                    let backoff = airdrop_backoff(attempt);
+                    eprintln!(
+                        "Airdrop rate limited, retrying in {}s (attempt {} of {})",
+                        backoff.as_secs(),
+                        attempt,
+                        self.max_retries
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(err) => {
+                    return Err(err)
+                        .with_context(|| format!("Airdrop of {} SOL failed", format_sol(lamports)))
+                }
+            }
+        };
+        let mut airdrop = AirdropRequested {
+            address: pubkey.to_string(),
+            lamports,
+            sol: format_sol(lamports),
+            signature: signature.to_string(),
+            status: None,
+            slot: None,
+            balance: None,
+        };
+        if self.wait {
+            let progress_bar = new_progress_bar(settings.output);
+            let confirmation = confirm_signature(rpc_client, signature, &progress_bar).await?;
+            airdrop.status = Some(confirmation.status);
+            airdrop.slot = Some(confirmation.slot);
+            airdrop.balance = Some(rpc_client.get_balance(pubkey).await?);
+        }
+        Ok(airdrop)
+    }
+}
+
+/// Most requests one airdrop is split into
+const MAX_AIRDROP_REQUESTS: u64 = 100;
+/// Longest wait between retries of a rate limited airdrop request
+const MAX_AIRDROP_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Most the public faucets hand out in one request
+fn faucet_cap(network: &Network) -> Option<u64> {
+    match network {
+        Network::Devnet => Some(5 * LAMPORTS_PER_SOL),
+        Network::Testnet => Some(LAMPORTS_PER_SOL),
+        Network::Mainnet | Network::Localnet | Network::Custom(_) => None,
+    }
+}
+
+fn split_airdrop(lamports: u64, max_per_request: Option<u64>) -> Result<Vec<u64>> {
+    match max_per_request {
+        Some(0) => anyhow::bail!("The amount per airdrop request must be greater than 0"),
+        Some(max) if lamports > max => {
+            let requests = lamports.div_ceil(max);
+            if requests > MAX_AIRDROP_REQUESTS {
+                anyhow::bail!(
+                    "Splitting {} SOL into requests of at most {} SOL takes {} requests, more than the limit of {}",
+                    format_sol(lamports),
+                    format_sol(max),
+                    requests,
+                    MAX_AIRDROP_REQUESTS
+                );
+            }
+            let mut chunks = vec![max; (lamports / max) as usize];
+            let remainder = lamports % max;
+            if remainder > 0 {
+                chunks.push(remainder);
+            }
+            Ok(chunks)
+        }
+        _ => Ok(vec![lamports]),
+    }
+}
+
+/// Exponential backoff before retry `attempt`, starting at 2 seconds
+fn airdrop_backoff(attempt: u32) -> Duration {
+    Duration::from_secs(2u64.saturating_pow(attempt)).min(MAX_AIRDROP_BACKOFF)
+}
+
+fn is_rate_limited(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("429")
+        || error.contains("too many requests")
+        || error.contains("rate limit")
+        || error.contains("airdrop limit")
 }
 
 impl TransferArgs {
-    pub async fn transfer_handler(&self) -> Result<()> {
-        let rpc_client = get_rpc_client(&self.network);
-        self.transfer_sol(rpc_client).await?;
+    pub async fn transfer_handler(&self, settings: &Settings) -> Result<()> {
+        let rpc_client = get_rpc_client(settings)?;
+        self.transfer_sol(rpc_client, settings).await?;
         Ok(())
     }
 
-    async fn transfer_sol(&self, rpc_client: RpcClient) -> Result<()> {
-        let progress_bar = ProgressBar::new(100);
-        progress_bar.set_style(
-            ProgressStyle::default_bar()
-                .template(
-                    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos:>7}% {msg}",
-                )
-                .unwrap_or_else(|_| ProgressStyle::default_bar())
-                .progress_chars("#>-"),
-        );
-        progress_bar.enable_steady_tick(Duration::from_millis(100));
+    /// The transfer followed by the memo, if any
+    fn transfer_instructions(&self, from: &Pubkey, to: &Pubkey, lamports: u64) -> Vec<Instruction> {
+        let mut instructions = vec![system_instruction::transfer(from, to, lamports)];
+        if let Some(memo) = &self.memo {
+            instructions.push(memo_instruction(memo, from));
+        }
+        instructions
+    }
 
-        let from_keypair = read_json_keypair_file(&self.from)?;
+    async fn transfer_sol(&self, rpc_client: RpcClient, settings: &Settings) -> Result<()> {
+        let progress_bar = new_progress_bar(settings.output);
+
+        let from_keypair = settings.keypair_or_default(&self.from)?.keypair()?;
         let from_pubkey = from_keypair.pubkey();
         let to_pubkey = Pubkey::from_str(&self.to)
             .with_context(|| format!("Invalid public key address: {}", &self.to))?;
 
-        // Update progress bar for creating transaction
         progress_bar.set_message("Creating transaction...");
-        progress_bar.inc(25);
+
+        let compute_budget = self
+            .priority_fee
+            .compute_budget(
+                &rpc_client,
+                &self.transfer_instructions(&from_pubkey, &to_pubkey, 0),
+                &from_pubkey,
+            )
+            .await?;
+        let recent_blockhash = rpc_client.get_latest_blockhash().await?;
 
         // Creating the transfer sol instruction
-        let transfer_value = self.value * LAMPORTS_PER_SOL as f64;
-        let ix = system_instruction::transfer(&from_pubkey, &to_pubkey, transfer_value as u64);
+        let lamports = match self.value {
+            Amount::Lamports(_) if self.keep_rent_exempt => {
+                anyhow::bail!("`--keep-rent-exempt` only applies to `--value ALL`")
+            }
+            Amount::Lamports(lamports) => lamports,
+            Amount::All => {
+                let reserve = rent_exempt_reserve(&rpc_client, self.keep_rent_exempt).await?;
+                sweepable_lamports(
+                    &rpc_client,
+                    &compute_budget.with_instructions(&self.transfer_instructions(
+                        &from_pubkey,
+                        &to_pubkey,
+                        0,
+                    )),
+                    &from_pubkey,
+                    &recent_blockhash,
+                    reserve,
+                )
+                .await?
+                .0
+            }
+        };
+        progress_bar.set_message("Checking recipient...");
+        let warnings = self
+            .recipient_checks
+            .check_recipient(&rpc_client, &to_pubkey, lamports)
+            .await?;
+        for warning in warnings {
+            progress_bar.suspend(|| eprintln!("Warning: {}", warning));
+        }
+        let guard = MainnetGuard::new(&rpc_client, settings).await?;
+        let transfers = [(to_pubkey, lamports)];
+        guard.check(&from_pubkey, &transfers)?;
 
-        // Putting the transfer sol instruction into a transaction
-        let recent_blockhash = rpc_client.get_latest_blockhash().await?;
+        let instructions = compute_budget.with_instructions(&self.transfer_instructions(
+            &from_pubkey,
+            &to_pubkey,
+            lamports,
+        ));
+        let message =
+            Message::new_with_blockhash(&instructions, Some(&from_pubkey), &recent_blockhash);
+        let fee = rpc_client.get_fee_for_message(&message).await?;
+
+        if self.dry_run {
+            progress_bar.set_message("Simulating transaction...");
+            let txn = Transaction::new(&[&from_keypair], message, recent_blockhash);
+            let mut simulation = simulate_transfer(
+                &rpc_client,
+                &txn,
+                &to_pubkey,
+                lamports,
+                fee,
+                compute_budget.priority_fee(),
+            )
+            .await?;
+            simulation.memo = self.memo.clone();
+            progress_bar.finish_and_clear();
+            print_output(&simulation, settings.output)?;
+            return Ok(());
+        }
 
-        // Update progress bar for signing transaction
-        progress_bar.set_message("Signing transaction...");
-        progress_bar.inc(25);
-        let txn = Transaction::new_signed_with_payer(
-            &[ix],
-            Some(&from_pubkey),
+        let summary = [
+            format!("From: {}", from_pubkey),
+            format!("To: {}", to_pubkey),
+            format!("Amount: {} SOL", format_sol(lamports)),
+            format!("Fee: {} SOL", format_sol(fee)),
+        ];
+        progress_bar.suspend(|| guard.confirm(&summary, self.yes))?;
+
+        let mut recorded = None;
+        let result = send_and_confirm_recorded(
+            &rpc_client,
+            &instructions,
+            &from_pubkey,
             &[&from_keypair],
-            recent_blockhash,
-        );
+            &progress_bar,
+            &mut |signature, _| guard.record(&from_pubkey, &transfers, &mut recorded, signature),
+        )
+        .await;
+        if let (Err(e), Some(signature)) = (&result, recorded) {
+            if did_not_land(e) {
+                guard.forget(&signature)?;
+            }
+        }
+        let confirmation = result?;
+        let output = TransferCompleted {
+            from: from_pubkey.to_string(),
+            to: to_pubkey.to_string(),
+            lamports,
+            sol: format_sol(lamports),
+            fee,
+            priority_fee: compute_budget.priority_fee(),
+            memo: self.memo.clone(),
+            signature: confirmation.signature.to_string(),
+            slot: confirmation.slot,
+            status: confirmation.status,
+            resigned: confirmation.resigned,
+        };
+        print_output(&output, settings.output)?;
+        Ok(())
+    }
+}
 
-        // Update progress bar for sending transaction
-        progress_bar.set_message("Sending transaction...");
-        progress_bar.inc(25);
+/// Simulates a signed transfer and collects the balances of both parties
+/// before and after it
+async fn simulate_transfer(
+    rpc_client: &RpcClient,
+    txn: &Transaction,
+    to_pubkey: &Pubkey,
+    lamports: u64,
+    fee: u64,
+    priority_fee: u64,
+) -> Result<TransferSimulation> {
+    let from_pubkey = txn.message.account_keys[0];
+    let addresses = [from_pubkey, *to_pubkey];
+    let pre_balances = rpc_client
+        .get_multiple_accounts(&addresses)
+        .await?
+        .into_iter()
+        .map(|account| account.map_or(0, |account| account.lamports))
+        .collect::<Vec<_>>();
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: true,
+        commitment: Some(rpc_client.commitment()),
+        accounts: Some(RpcSimulateTransactionAccountsConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            addresses: addresses.iter().map(|a| a.to_string()).collect(),
+        }),
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let result = rpc_client
+        .simulate_transaction_with_config(txn, config)
+        .await?
+        .value;
+    let post_balances = result
+        .accounts
+        .unwrap_or_default()
+        .into_iter()
+        .map(|account| account.map(|account| account.lamports))
+        .collect::<Vec<_>>();
+    Ok(TransferSimulation {
+        from: from_pubkey.to_string(),
+        to: to_pubkey.to_string(),
+        lamports,
+        sol: format_sol(lamports),
+        fee,
+        priority_fee,
+        units_consumed: result.units_consumed,
+        success: result.err.is_none(),
+        error: result.err.map(|err| err.to_string()),
+        from_balance_before: pre_balances[0],
+        from_balance_after: post_balances.first().copied().flatten(),
+        to_balance_before: pre_balances[1],
+        to_balance_after: post_balances.get(1).copied().flatten(),
+        memo: None,
+        logs: result.logs.unwrap_or_default(),
+    })
+}
 
-        let signature = rpc_client.send_and_confirm_transaction(&txn).await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Finalize progress bar
-        progress_bar.set_message("Finalizing transaction...");
-        progress_bar.finish_with_message("Transaction completed");
-        println!(
-            "Transfer successful, signature: {}",
-            format!("{}", &signature).yellow().bold()
+    #[test]
+    fn split_airdrop_fits_under_the_cap() {
+        let sol = LAMPORTS_PER_SOL;
+        assert_eq!(split_airdrop(3 * sol, None).unwrap(), vec![3 * sol]);
+        assert_eq!(
+            split_airdrop(3 * sol, Some(5 * sol)).unwrap(),
+            vec![3 * sol]
         );
-        Ok(())
+        assert_eq!(
+            split_airdrop(5 * sol, Some(5 * sol)).unwrap(),
+            vec![5 * sol]
+        );
+        assert_eq!(
+            split_airdrop(10 * sol, Some(5 * sol)).unwrap(),
+            vec![5 * sol, 5 * sol]
+        );
+        assert_eq!(
+            split_airdrop(12 * sol + 1, Some(5 * sol)).unwrap(),
+            vec![5 * sol, 5 * sol, 2 * sol + 1]
+        );
+    }
+
+    #[test]
+    fn split_airdrop_keeps_the_total() {
+        let chunks = split_airdrop(7_777_777_777, Some(LAMPORTS_PER_SOL)).unwrap();
+        assert_eq!(chunks.len(), 8);
+        assert_eq!(chunks.iter().sum::<u64>(), 7_777_777_777);
+        assert!(chunks.iter().all(|&chunk| chunk <= LAMPORTS_PER_SOL));
+    }
+
+    #[test]
+    fn split_airdrop_rejects_a_zero_cap() {
+        assert!(split_airdrop(LAMPORTS_PER_SOL, Some(0)).is_err());
+    }
+
+    #[test]
+    fn split_airdrop_limits_the_number_of_requests() {
+        assert_eq!(split_airdrop(100, Some(1)).unwrap().len(), 100);
+        let err = split_airdrop(LAMPORTS_PER_SOL, Some(1)).unwrap_err();
+        assert!(err.to_string().contains("more than the limit of 100"));
+    }
+
+    #[test]
+    fn airdrop_backoff_is_capped() {
+        assert_eq!(airdrop_backoff(1), Duration::from_secs(2));
+        assert_eq!(airdrop_backoff(5), Duration::from_secs(32));
+        assert_eq!(airdrop_backoff(6), MAX_AIRDROP_BACKOFF);
+        assert_eq!(airdrop_backoff(u32::MAX), MAX_AIRDROP_BACKOFF);
+    }
+
+    #[test]
+    fn detects_rate_limits() {
+        assert!(is_rate_limited(
+            "HTTP status client error (429 Too Many Requests)"
+        ));
+        assert!(is_rate_limited("airdrop limit reached for today"));
+        assert!(!is_rate_limited("Invalid request"));
     }
 }
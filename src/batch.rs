@@ -0,0 +1,908 @@
+use crate::amount::{format_sol, Amount};
+use crate::args::get_rpc_client;
+use crate::config::Settings;
+use crate::output::{print_output, CommandOutput};
+use crate::policy::MainnetGuard;
+use crate::priority_fee::{ComputeBudget, PriorityFeeArgs};
+use crate::recipient::RecipientCheckArgs;
+use crate::sender::{
+    did_not_land, is_preflight_failure, new_progress_bar, send_and_confirm_recorded,
+};
+use crate::signer::SignerSource;
+use anyhow::{Context, Result};
+use clap::Args;
+use colored::*;
+use futures::stream::{self, StreamExt};
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use solana_sdk::system_instruction;
+use solana_transaction_status::TransactionStatus;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How many signatures `getSignatureStatuses` accepts per request
+const MAX_SIGNATURE_STATUSES: usize = 256;
+
+#[derive(Args, Clone, Debug)]
+pub struct TransferBatchArgs {
+    /// CSV or JSON file of payouts, every row has an `address` and an
+    /// `amount` e.g 1.5, 1.5SOL or 2500lamports
+    #[arg(long)]
+    pub file: PathBuf,
+    /// The keypair file or signer URI for the wallet paying out, defaults to
+    /// the keypair in your config profile
+    #[arg(short, long)]
+    pub from: Option<SignerSource>,
+    /// Where the result of every row is recorded so a rerun skips paid rows,
+    /// defaults to the payout file with a `.state.json` suffix
+    #[arg(long)]
+    pub state_file: Option<PathBuf>,
+    /// How many transfers are packed into each transaction
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u8).range(1..=20))]
+    pub batch_size: u8,
+    /// How many transactions are in flight at once
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u8).range(1..=32))]
+    pub concurrency: u8,
+    /// Validate the file and show what would be paid without sending anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Send on mainnet without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+    #[command(flatten)]
+    pub recipient_checks: RecipientCheckArgs,
+    #[command(flatten)]
+    pub priority_fee: PriorityFeeArgs,
+}
+
+/// A validated row of the payout file
+#[derive(Clone, Debug)]
+struct Payout {
+    /// 1-based, not counting the CSV header
+    row: usize,
+    recipient: Pubkey,
+    lamports: u64,
+}
+
+#[derive(Deserialize)]
+struct PayoutRecord {
+    address: String,
+    amount: String,
+}
+
+/// JSON amounts can be numbers as well as strings
+#[derive(Deserialize)]
+struct JsonPayoutRecord {
+    address: String,
+    amount: serde_json::Value,
+}
+
+fn read_payout_records(path: &Path) -> Result<Vec<PayoutRecord>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read payout file {}", path.display()))?;
+    let is_json = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+    if is_json {
+        let records: Vec<JsonPayoutRecord> =
+            serde_json::from_str(&contents).with_context(|| {
+                format!(
+                    "Invalid payout file {}, expected an array of objects with an `address` and an `amount`",
+                    path.display()
+                )
+            })?;
+        return Ok(records
+            .into_iter()
+            .map(|record| PayoutRecord {
+                address: record.address,
+                amount: match record.amount {
+                    serde_json::Value::String(amount) => amount,
+                    amount => amount.to_string(),
+                },
+            })
+            .collect());
+    }
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(contents.as_bytes())
+        .deserialize()
+        .enumerate()
+        .map(|(i, record)| {
+            record.with_context(|| {
+                format!(
+                    "Invalid row {} in {}, expected `address` and `amount` columns",
+                    i + 1,
+                    path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+/// Checks every row, failing with all the invalid ones at once
+fn validate_payouts(records: Vec<PayoutRecord>) -> Result<Vec<Payout>> {
+    let mut payouts = Vec::new();
+    let mut errors = Vec::new();
+    for (i, record) in records.into_iter().enumerate() {
+        let row = i + 1;
+        let recipient = Pubkey::from_str(&record.address)
+            .map_err(|_| format!("row {}: invalid address `{}`", row, record.address));
+        let lamports = Amount::from_str(&record.amount)
+            .and_then(|amount| amount.lamports("a payout"))
+            .map_err(|e| format!("row {}: {}", row, e))
+            .and_then(|lamports| match lamports {
+                0 => Err(format!("row {}: amount is 0", row)),
+                lamports => Ok(lamports),
+            });
+        match (recipient, lamports) {
+            (Ok(recipient), Ok(lamports)) => payouts.push(Payout {
+                row,
+                recipient,
+                lamports,
+            }),
+            (recipient, lamports) => {
+                errors.extend(recipient.err().into_iter().chain(lamports.err()))
+            }
+        }
+    }
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "The payout file has {} invalid row(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+    if payouts.is_empty() {
+        anyhow::bail!("The payout file has no rows");
+    }
+    Ok(payouts)
+}
+
+/// `payouts.csv` -> `payouts.csv.state.json`
+fn default_state_file(file: &Path) -> PathBuf {
+    let mut state_file = file.as_os_str().to_owned();
+    state_file.push(".state.json");
+    PathBuf::from(state_file)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum RowStatus {
+    /// Signed and maybe sent, whether it landed is not known yet
+    Pending,
+    Confirmed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct RowState {
+    address: String,
+    lamports: u64,
+    status: RowStatus,
+    /// Every signature the row was sent with, re-signing after the blockhash
+    /// expired adds one
+    signatures: Vec<String>,
+    /// The last block height the latest signature can land at
+    last_valid_block_height: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The resumable record of a payout file, keyed by row
+#[derive(Serialize, Deserialize, Debug)]
+struct BatchState {
+    from: String,
+    rows: BTreeMap<usize, RowState>,
+}
+
+impl BatchState {
+    fn load(path: &Path, from: &Pubkey) -> Result<Self> {
+        if !path.exists() {
+            return Ok(BatchState {
+                from: from.to_string(),
+                rows: BTreeMap::new(),
+            });
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read state file {}", path.display()))?;
+        let state: BatchState = serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid state file {}", path.display()))?;
+        if state.from != from.to_string() {
+            anyhow::bail!(
+                "State file {} belongs to payouts from {}, not {}",
+                path.display(),
+                state.from,
+                from
+            );
+        }
+        Ok(state)
+    }
+
+    /// Writes the state through a temporary file so an interrupted write
+    /// never leaves it truncated
+    fn save(&self, path: &Path) -> Result<()> {
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("Invalid state file path {}", path.display()))?;
+        let temp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+        std::fs::write(&temp_path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write state file {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, path)
+            .with_context(|| format!("Failed to write state file {}", path.display()))
+    }
+
+    /// Fails when the payout file changed since the state was recorded, as
+    /// rows would no longer line up
+    fn check(&self, payouts: &[Payout]) -> Result<()> {
+        for (row, state) in &self.rows {
+            let matches = payouts.get(row - 1).is_some_and(|payout| {
+                payout.recipient.to_string() == state.address && payout.lamports == state.lamports
+            });
+            if !matches {
+                anyhow::bail!(
+                    "Row {} of the payout file no longer matches the state file, which recorded {} SOL to {}. Restore the payout file or use a new --state-file",
+                    row,
+                    format_sol(state.lamports),
+                    state.address
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn is_confirmed(&self, row: usize) -> bool {
+        self.rows
+            .get(&row)
+            .is_some_and(|state| state.status == RowStatus::Confirmed)
+    }
+
+    /// Settles the rows an interrupted run left pending. Rows that landed are
+    /// confirmed, rows that can no longer land are dropped to be sent again,
+    /// and rows that may still land stop the run as resending them could pay
+    /// twice.
+    async fn settle_pending(&mut self, rpc_client: &RpcClient) -> Result<()> {
+        let signatures = self
+            .rows
+            .values()
+            .filter(|state| state.status == RowStatus::Pending)
+            .flat_map(|state| &state.signatures)
+            .map(|signature| {
+                Signature::from_str(signature)
+                    .with_context(|| format!("Invalid signature {} in the state file", signature))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if signatures.is_empty() {
+            return Ok(());
+        }
+        let mut statuses = HashMap::new();
+        for chunk in signatures.chunks(MAX_SIGNATURE_STATUSES) {
+            let chunk_statuses = rpc_client
+                .get_signature_statuses_with_history(chunk)
+                .await
+                .context("Failed to look up the signatures of an earlier run")?
+                .value;
+            for (signature, status) in chunk.iter().zip(chunk_statuses) {
+                if let Some(status) = status {
+                    statuses.insert(signature.to_string(), status);
+                }
+            }
+        }
+        let block_height = rpc_client.get_block_height().await?;
+        self.settle(&statuses, block_height, rpc_client.commitment())
+    }
+
+    /// The part of `settle_pending` after the lookups, `statuses` holds the
+    /// signatures the cluster has seen
+    fn settle(
+        &mut self,
+        statuses: &HashMap<String, TransactionStatus>,
+        block_height: u64,
+        commitment: CommitmentConfig,
+    ) -> Result<()> {
+        let mut in_flight = Vec::new();
+        let mut retry = Vec::new();
+        for (row, state) in self
+            .rows
+            .iter_mut()
+            .filter(|(_, state)| state.status == RowStatus::Pending)
+        {
+            let landed = state
+                .signatures
+                .iter()
+                .filter_map(|signature| statuses.get(signature))
+                .find(|status| status.err.is_none());
+            match landed {
+                Some(status) if status.satisfies_commitment(commitment) => {
+                    state.status = RowStatus::Confirmed;
+                    state.error = None;
+                }
+                Some(_) => in_flight.push(*row),
+                None => {
+                    let latest_failed = state
+                        .signatures
+                        .last()
+                        .is_some_and(|signature| statuses.contains_key(signature));
+                    if latest_failed || block_height > state.last_valid_block_height {
+                        retry.push(*row);
+                    } else {
+                        in_flight.push(*row);
+                    }
+                }
+            }
+        }
+        if !in_flight.is_empty() {
+            let rows = in_flight
+                .iter()
+                .map(|row| row.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            anyhow::bail!(
+                "Row(s) {} were sent by an earlier run and may still land, rerun in a minute once they have settled",
+                rows
+            );
+        }
+        for row in retry {
+            self.rows.remove(&row);
+        }
+        Ok(())
+    }
+}
+
+fn transfer_instructions(from: &Pubkey, payouts: &[Payout]) -> Vec<Instruction> {
+    payouts
+        .iter()
+        .map(|payout| system_instruction::transfer(from, &payout.recipient, payout.lamports))
+        .collect()
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PayoutStatus {
+    /// Paid by this run
+    Confirmed,
+    /// Paid by an earlier run
+    Skipped,
+    /// Not paid, a rerun retries it
+    Failed,
+    /// Would be paid, for a dry run
+    Planned,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PayoutResult {
+    pub row: usize,
+    pub address: String,
+    pub lamports: u64,
+    pub status: PayoutStatus,
+    pub signature: Option<String>,
+    pub error: Option<String>,
+}
+
+impl PayoutResult {
+    fn new(payout: &Payout, status: PayoutStatus) -> Self {
+        PayoutResult {
+            row: payout.row,
+            address: payout.recipient.to_string(),
+            lamports: payout.lamports,
+            status,
+            signature: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TransferBatchReport {
+    pub from: String,
+    pub file: String,
+    pub state_file: String,
+    pub dry_run: bool,
+    pub rows: Vec<PayoutResult>,
+    /// Lamports paid by this run, or that would be paid for a dry run
+    pub lamports: u64,
+    pub confirmed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub transactions: usize,
+    /// Total fee in lamports of the confirmed transactions, estimated for a
+    /// dry run
+    pub fee: u64,
+}
+
+impl fmt::Display for TransferBatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.dry_run {
+            let planned = self.rows.len() - self.skipped;
+            writeln!(
+                f,
+                "Would pay {} row(s), {} SOL in {} transaction(s)",
+                planned,
+                format_sol(self.lamports).green().bold(),
+                self.transactions
+            )?;
+            writeln!(f, "Estimated fee: {} SOL", format_sol(self.fee))?;
+        } else {
+            writeln!(
+                f,
+                "Paid {} row(s), {} SOL",
+                self.confirmed,
+                format_sol(self.lamports).green().bold()
+            )?;
+            writeln!(f, "Fee: {} SOL", format_sol(self.fee))?;
+        }
+        if self.skipped > 0 {
+            writeln!(f, "Skipped {} row(s) paid by an earlier run", self.skipped)?;
+        }
+        if self.failed > 0 {
+            writeln!(
+                f,
+                "{} {} row(s), rerun to retry them:",
+                "Failed".red().bold(),
+                self.failed
+            )?;
+            for row in self
+                .rows
+                .iter()
+                .filter(|r| r.status == PayoutStatus::Failed)
+            {
+                writeln!(
+                    f,
+                    "  row {}: {} SOL to {}: {}",
+                    row.row,
+                    format_sol(row.lamports),
+                    row.address,
+                    row.error.as_deref().unwrap_or_default()
+                )?;
+            }
+        }
+        write!(f, "State file: {}", self.state_file)
+    }
+}
+
+impl CommandOutput for TransferBatchReport {
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        for row in &self.rows {
+            writer.serialize(row)?;
+        }
+        Ok(())
+    }
+}
+
+impl TransferBatchArgs {
+    pub async fn transfer_batch_handler(&self, settings: &Settings) -> Result<()> {
+        let rpc_client = get_rpc_client(settings)?;
+        self.transfer_batch(&rpc_client, settings).await
+    }
+
+    async fn transfer_batch(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        let from = settings.keypair_or_default(&self.from)?.keypair()?;
+        let payouts = validate_payouts(read_payout_records(&self.file)?)?;
+        let state_file = self
+            .state_file
+            .clone()
+            .unwrap_or_else(|| default_state_file(&self.file));
+        let mut state = BatchState::load(&state_file, &from.pubkey())?;
+        state.check(&payouts)?;
+
+        let progress_bar = new_progress_bar(settings.output);
+        progress_bar.set_message("Checking rows sent by an earlier run...");
+        state.settle_pending(rpc_client).await?;
+        if !self.dry_run {
+            state.save(&state_file)?;
+        }
+        let (paid, unpaid): (Vec<_>, Vec<_>) = payouts
+            .into_iter()
+            .partition(|payout| state.is_confirmed(payout.row));
+        let batches = unpaid.chunks(self.batch_size as usize).collect::<Vec<_>>();
+
+        // Every batch is priced like the first, the largest one
+        progress_bar.set_message("Estimating fees...");
+        let mut compute_budget = ComputeBudget::default();
+        let mut fee_per_transaction = 0;
+        if let Some(batch) = batches.first() {
+            let transfers = transfer_instructions(&from.pubkey(), batch);
+            compute_budget = self
+                .priority_fee
+                .compute_budget(rpc_client, &transfers, &from.pubkey())
+                .await?;
+            let recent_blockhash = rpc_client.get_latest_blockhash().await?;
+            let message = Message::new_with_blockhash(
+                &compute_budget.with_instructions(&transfers),
+                Some(&from.pubkey()),
+                &recent_blockhash,
+            );
+            fee_per_transaction = rpc_client.get_fee_for_message(&message).await?;
+        }
+        let lamports = unpaid
+            .iter()
+            .try_fold(0u64, |total, payout| total.checked_add(payout.lamports))
+            .context("The payouts add up to more SOL than exists")?;
+        let fee = fee_per_transaction * batches.len() as u64;
+        let balance = rpc_client.get_balance(&from.pubkey()).await?;
+        if lamports.saturating_add(fee) > balance {
+            anyhow::bail!(
+                "Insufficient balance, the payouts need {} SOL plus {} SOL of fees but {} SOL is available",
+                format_sol(lamports),
+                format_sol(fee),
+                format_sol(balance)
+            );
+        }
+
+        let transfers = unpaid
+            .iter()
+            .map(|payout| (payout.recipient, payout.lamports))
+            .collect::<Vec<_>>();
+        // Every row is checked so a bad file is reported in full at once
+        progress_bar.set_message("Checking recipients...");
+        let outcomes = self
+            .recipient_checks
+            .check_recipients(rpc_client, &transfers)
+            .await?;
+        let mut problems = Vec::new();
+        for (payout, outcome) in unpaid.iter().zip(outcomes) {
+            match outcome {
+                Ok(warnings) => {
+                    for warning in warnings {
+                        progress_bar
+                            .suspend(|| eprintln!("Warning: row {}: {}", payout.row, warning));
+                    }
+                }
+                Err(e) => problems.push(format!("row {}: {}", payout.row, e)),
+            }
+        }
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "The payout file has {} row(s) with a suspicious recipient:\n  {}",
+                problems.len(),
+                problems.join("\n  ")
+            );
+        }
+
+        let guard = MainnetGuard::new(rpc_client, settings).await?;
+        guard.check(&from.pubkey(), &transfers)?;
+
+        let mut rows = paid
+            .iter()
+            .map(|payout| PayoutResult::new(payout, PayoutStatus::Skipped))
+            .collect::<Vec<_>>();
+        if self.dry_run {
+            progress_bar.finish_and_clear();
+            rows.extend(
+                unpaid
+                    .iter()
+                    .map(|payout| PayoutResult::new(payout, PayoutStatus::Planned)),
+            );
+            rows.sort_by_key(|row| row.row);
+            let output = TransferBatchReport {
+                from: from.pubkey().to_string(),
+                file: self.file.display().to_string(),
+                state_file: state_file.display().to_string(),
+                dry_run: true,
+                rows,
+                lamports,
+                confirmed: 0,
+                skipped: paid.len(),
+                failed: 0,
+                transactions: batches.len(),
+                fee,
+            };
+            return print_output(&output, settings.output);
+        }
+
+        let summary = [
+            format!("From: {}", from.pubkey()),
+            format!(
+                "Payouts: {} row(s) from {}",
+                unpaid.len(),
+                self.file.display()
+            ),
+            format!("Amount: {} SOL", format_sol(lamports)),
+            format!("Fee: {} SOL", format_sol(fee)),
+        ];
+        progress_bar.suspend(|| guard.confirm(&summary, self.yes))?;
+
+        let state = RefCell::new(state);
+        let sent = Cell::new(0);
+        progress_bar.set_position(0);
+        progress_bar.set_message(format!("Sent 0 of {} transactions", batches.len()));
+        let results = stream::iter(batches.iter().map(|batch| {
+            let state = &state;
+            let state_file = &state_file;
+            let from = &from;
+            let progress_bar = &progress_bar;
+            let guard = &guard;
+            let sent = &sent;
+            let batch_count = batches.len();
+            async move {
+                let results = send_batch(
+                    rpc_client,
+                    from,
+                    batch,
+                    &compute_budget,
+                    state,
+                    state_file,
+                    guard,
+                )
+                .await;
+                sent.set(sent.get() + 1);
+                progress_bar.set_position((sent.get() * 100 / batch_count) as u64);
+                progress_bar.set_message(format!(
+                    "Sent {} of {} transactions",
+                    sent.get(),
+                    batch_count
+                ));
+                results
+            }
+        }))
+        .buffer_unordered(self.concurrency as usize)
+        .collect::<Vec<_>>()
+        .await;
+        progress_bar.finish_and_clear();
+        for result in results {
+            rows.extend(result?);
+        }
+        rows.sort_by_key(|row| row.row);
+
+        let confirmed_rows = rows
+            .iter()
+            .filter(|row| row.status == PayoutStatus::Confirmed);
+        let confirmed_transactions = confirmed_rows
+            .clone()
+            .filter_map(|row| row.signature.as_deref())
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+        let output = TransferBatchReport {
+            from: from.pubkey().to_string(),
+            file: self.file.display().to_string(),
+            state_file: state_file.display().to_string(),
+            dry_run: false,
+            lamports: confirmed_rows.clone().map(|row| row.lamports).sum(),
+            confirmed: confirmed_rows.count(),
+            skipped: paid.len(),
+            failed: rows
+                .iter()
+                .filter(|row| row.status == PayoutStatus::Failed)
+                .count(),
+            transactions: confirmed_transactions,
+            fee: fee_per_transaction * confirmed_transactions as u64,
+            rows,
+        };
+        print_output(&output, settings.output)?;
+        if output.failed > 0 {
+            anyhow::bail!("{} payout(s) failed, rerun to retry them", output.failed);
+        }
+        Ok(())
+    }
+}
+
+/// Sends one batch of payouts, recording every signature in the state file
+/// before it is sent. Only failing to write the state file is an error, a
+/// failed transaction fails its rows.
+async fn send_batch(
+    rpc_client: &RpcClient,
+    from: &Keypair,
+    batch: &[Payout],
+    compute_budget: &ComputeBudget,
+    state: &RefCell<BatchState>,
+    state_file: &Path,
+    guard: &MainnetGuard,
+) -> Result<Vec<PayoutResult>> {
+    let instructions =
+        compute_budget.with_instructions(&transfer_instructions(&from.pubkey(), batch));
+    let transfers = batch
+        .iter()
+        .map(|payout| (payout.recipient, payout.lamports))
+        .collect::<Vec<_>>();
+    let mut recorded = None;
+    let mut on_signed = |signature: &Signature, last_valid_block_height: u64| {
+        guard.record(&from.pubkey(), &transfers, &mut recorded, signature)?;
+        let mut state = state.borrow_mut();
+        for payout in batch {
+            let row = state.rows.entry(payout.row).or_insert_with(|| RowState {
+                address: payout.recipient.to_string(),
+                lamports: payout.lamports,
+                status: RowStatus::Pending,
+                signatures: Vec::new(),
+                last_valid_block_height,
+                error: None,
+            });
+            row.signatures.push(signature.to_string());
+            row.last_valid_block_height = last_valid_block_height;
+        }
+        state.save(state_file)
+    };
+    // Each transaction's own progress is hidden behind the overall bar
+    let result = send_and_confirm_recorded(
+        rpc_client,
+        &instructions,
+        &from.pubkey(),
+        &[from],
+        &ProgressBar::hidden(),
+        &mut on_signed,
+    )
+    .await;
+    // Rows that may still land stay in the ledger as well as pending
+    if let (Err(e), Some(signature)) = (&result, recorded) {
+        if did_not_land(e) {
+            guard.forget(&signature)?;
+        }
+    }
+
+    let mut state = state.borrow_mut();
+    // Earlier signatures of the batch had already expired unseen before the
+    // one refused in preflight was signed
+    let never_sent = result.as_ref().err().is_some_and(is_preflight_failure);
+    let mut results = Vec::new();
+    for payout in batch {
+        let mut result_row = PayoutResult::new(payout, PayoutStatus::Failed);
+        match &result {
+            Ok(confirmation) => {
+                result_row.status = PayoutStatus::Confirmed;
+                result_row.signature = Some(confirmation.signature.to_string());
+            }
+            Err(e) => result_row.error = Some(e.to_string()),
+        }
+        if never_sent {
+            state.rows.remove(&payout.row);
+        } else if let Some(row) = state.rows.get_mut(&payout.row) {
+            // Other failed rows stay pending, the next run settles them on chain
+            match &result {
+                Ok(_) => {
+                    row.status = RowStatus::Confirmed;
+                    row.error = None;
+                }
+                Err(e) => row.error = Some(e.to_string()),
+            }
+        }
+        results.push(result_row);
+    }
+    state.save(state_file)?;
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use solana_sdk::transaction::TransactionError;
+    use solana_transaction_status::TransactionConfirmationStatus;
+
+    fn payout(row: usize, lamports: u64) -> Payout {
+        Payout {
+            row,
+            recipient: Pubkey::new_unique(),
+            lamports,
+        }
+    }
+
+    fn pending(payout: &Payout, signatures: &[Signature]) -> RowState {
+        RowState {
+            address: payout.recipient.to_string(),
+            lamports: payout.lamports,
+            status: RowStatus::Pending,
+            signatures: signatures.iter().map(ToString::to_string).collect(),
+            last_valid_block_height: 100,
+            error: None,
+        }
+    }
+
+    fn state(rows: Vec<(usize, RowState)>) -> BatchState {
+        BatchState {
+            from: Pubkey::new_unique().to_string(),
+            rows: rows.into_iter().collect(),
+        }
+    }
+
+    fn status(
+        err: Option<TransactionError>,
+        confirmation_status: TransactionConfirmationStatus,
+    ) -> TransactionStatus {
+        TransactionStatus {
+            slot: 1,
+            confirmations: Some(1),
+            status: err.clone().map_or(Result::Ok(()), Err),
+            err,
+            confirmation_status: Some(confirmation_status),
+        }
+    }
+
+    fn settle(
+        state: &mut BatchState,
+        statuses: &[(Signature, TransactionStatus)],
+        block_height: u64,
+    ) -> Result<()> {
+        let statuses = statuses
+            .iter()
+            .map(|(signature, status)| (signature.to_string(), status.clone()))
+            .collect();
+        state.settle(&statuses, block_height, CommitmentConfig::confirmed())
+    }
+
+    #[test]
+    fn state_file_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("payouts.csv.state.json");
+        let from = Pubkey::new_unique();
+        let mut saved = BatchState::load(&path, &from).unwrap();
+        assert!(saved.rows.is_empty());
+        let first = payout(1, 5);
+        saved
+            .rows
+            .insert(1, pending(&first, &[Signature::new_unique()]));
+        saved.save(&path).unwrap();
+
+        let loaded = BatchState::load(&path, &from).unwrap();
+        assert_eq!(loaded.rows[&1].status, RowStatus::Pending);
+        assert_eq!(loaded.rows[&1].signatures, saved.rows[&1].signatures);
+
+        let err = BatchState::load(&path, &Pubkey::new_unique()).unwrap_err();
+        assert!(err.to_string().contains("belongs to payouts from"));
+    }
+
+    #[test]
+    fn check_rejects_a_changed_payout_file() {
+        let payouts = [payout(1, 5), payout(2, 7)];
+        let state = state(vec![(2, pending(&payouts[1], &[]))]);
+        assert!(state.check(&payouts).is_ok());
+
+        let changed = [payouts[0].clone(), payout(2, 7)];
+        assert!(state.check(&changed).is_err());
+        assert!(state.check(&payouts[..1]).is_err());
+    }
+
+    #[test]
+    fn settle_confirms_rows_that_landed() {
+        let row = payout(1, 5);
+        let (expired, resigned) = (Signature::new_unique(), Signature::new_unique());
+        let mut state = state(vec![(1, pending(&row, &[expired, resigned]))]);
+        let landed = status(None, TransactionConfirmationStatus::Confirmed);
+        settle(&mut state, &[(resigned, landed)], 90).unwrap();
+        assert!(state.is_confirmed(1));
+    }
+
+    #[test]
+    fn settle_stops_on_rows_that_may_still_land() {
+        let (first, second) = (payout(1, 5), payout(2, 7));
+        let (processed, unseen) = (Signature::new_unique(), Signature::new_unique());
+        let mut state = state(vec![
+            (1, pending(&first, &[processed])),
+            (2, pending(&second, &[unseen])),
+        ]);
+        let statuses = [(
+            processed,
+            status(None, TransactionConfirmationStatus::Processed),
+        )];
+        let err = settle(&mut state, &statuses, 100).unwrap_err();
+        assert!(err.to_string().starts_with("Row(s) 1, 2 were sent"));
+        assert_eq!(state.rows[&1].status, RowStatus::Pending);
+        assert_eq!(state.rows[&2].status, RowStatus::Pending);
+    }
+
+    #[test]
+    fn settle_drops_rows_that_can_no_longer_land() {
+        let (first, second) = (payout(1, 5), payout(2, 7));
+        let (failed, expired) = (Signature::new_unique(), Signature::new_unique());
+        let mut state = state(vec![
+            (1, pending(&first, &[failed])),
+            (2, pending(&second, &[expired])),
+        ]);
+        // The failed row is still within its blockhash, the unseen one is not
+        state.rows.get_mut(&2).unwrap().last_valid_block_height = 90;
+        let error = Some(TransactionError::InsufficientFundsForFee);
+        let statuses = [(
+            failed,
+            status(error, TransactionConfirmationStatus::Finalized),
+        )];
+        settle(&mut state, &statuses, 100).unwrap();
+        assert!(state.rows.is_empty());
+    }
+}
@@ -0,0 +1,442 @@
+use crate::amount::{format_sol, format_token_amount, TokenAmount};
+use crate::args::get_rpc_client;
+use crate::config::Settings;
+use crate::output::{print_output, CommandOutput};
+use crate::policy::MainnetGuard;
+use crate::priority_fee::PriorityFeeArgs;
+use crate::sender::new_progress_bar;
+use crate::signer::SignerSource;
+use crate::token::{
+    get_token_accounts, parse_pubkey, price_token_transaction, program_name,
+    send_token_transaction, TokenAccount,
+};
+use anyhow::Result;
+use clap::Args;
+use colored::*;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::Signer;
+use std::fmt;
+
+/// How many instructions a cleanup transaction holds, a dust account takes
+/// two, a burn and a close
+const MAX_INSTRUCTIONS_PER_TRANSACTION: usize = 20;
+
+#[derive(Args, Clone, Debug)]
+pub struct CleanupArgs {
+    /// The keypair file or signer URI of the wallet to clean up, defaults to
+    /// the keypair in your config profile
+    #[arg(short, long)]
+    pub keypair: Option<SignerSource>,
+    /// Burn balances of at most this many tokens e.g 0.001 first, so their
+    /// accounts can be closed too
+    #[arg(long, value_name = "MAX_AMOUNT")]
+    pub burn_dust: Option<TokenAmount>,
+    /// List the accounts that would be closed without sending anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Burn dust on mainnet without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+    #[command(flatten)]
+    pub priority_fee: PriorityFeeArgs,
+}
+
+/// A token account picked for closing
+struct Closable {
+    address: Pubkey,
+    program_id: Pubkey,
+    mint: Pubkey,
+    decimals: u8,
+    /// The account's lamports, returned to the wallet on closing
+    rent: u64,
+    /// The dust to burn before closing, 0 for empty accounts
+    burn: u64,
+}
+
+impl Closable {
+    fn new(account: &TokenAccount, burn: u64) -> Result<Self> {
+        Ok(Self {
+            address: account.address,
+            program_id: account.program_id,
+            mint: parse_pubkey(&account.info.mint, "mint")?,
+            decimals: account.info.token_amount.decimals,
+            rent: account.lamports,
+            burn,
+        })
+    }
+
+    fn instructions(&self, owner: &Pubkey) -> Result<Vec<Instruction>> {
+        let mut instructions = Vec::new();
+        if self.burn > 0 {
+            instructions.push(spl_token_2022::instruction::burn_checked(
+                &self.program_id,
+                &self.address,
+                &self.mint,
+                owner,
+                &[],
+                self.burn,
+                self.decimals,
+            )?);
+        }
+        instructions.push(spl_token_2022::instruction::close_account(
+            &self.program_id,
+            &self.address,
+            owner,
+            owner,
+            &[],
+        )?);
+        Ok(instructions)
+    }
+}
+
+/// Splits `closable` into batches that fit in a transaction
+fn batches(closable: &[Closable]) -> Vec<&[Closable]> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut instructions = 0;
+    for (i, account) in closable.iter().enumerate() {
+        let cost = if account.burn > 0 { 2 } else { 1 };
+        if instructions + cost > MAX_INSTRUCTIONS_PER_TRANSACTION {
+            batches.push(&closable[start..i]);
+            start = i;
+            instructions = 0;
+        }
+        instructions += cost;
+    }
+    if start < closable.len() {
+        batches.push(&closable[start..]);
+    }
+    batches
+}
+
+#[derive(Serialize, Debug)]
+pub struct ClosedAccount {
+    pub account: String,
+    pub mint: String,
+    pub program: &'static str,
+    /// Raw dust amount burned before closing, 0 for empty accounts
+    pub burned: u64,
+    pub ui_burned: String,
+    /// Rent lamports returned to the wallet
+    pub rent: u64,
+    /// The transaction that closed the account, unset for a dry run
+    pub signature: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CleanupReport {
+    pub owner: String,
+    pub dry_run: bool,
+    pub accounts: Vec<ClosedAccount>,
+    /// Empty or dust accounts left open because they are frozen or only
+    /// another wallet can close them
+    pub skipped: usize,
+    /// Total rent lamports returned to the wallet
+    pub reclaimed: u64,
+    /// Total fee in lamports of every transaction, estimated for a dry run
+    pub fee: u64,
+    pub transactions: usize,
+    /// Accounts left open because a transaction failed, the run stops at the
+    /// first failure
+    pub left_open: usize,
+}
+
+impl fmt::Display for CleanupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.accounts.is_empty() && self.left_open == 0 {
+            write!(f, "No token accounts to close")?;
+        } else {
+            let verb = if self.dry_run {
+                "Would close"
+            } else {
+                "Closed"
+            };
+            writeln!(f, "{} {} token account(s):", verb, self.accounts.len())?;
+            for account in &self.accounts {
+                write!(
+                    f,
+                    "  {} {} ({})",
+                    account.account.blue(),
+                    account.mint,
+                    account.program
+                )?;
+                if account.burned > 0 {
+                    write!(f, " burning {} tokens", account.ui_burned)?;
+                }
+                writeln!(f, ", {} SOL", format_sol(account.rent))?;
+            }
+            let reclaimed = if self.dry_run {
+                "Reclaimable"
+            } else {
+                "Reclaimed"
+            };
+            writeln!(
+                f,
+                "{}: {} SOL",
+                reclaimed,
+                format_sol(self.reclaimed).green().bold()
+            )?;
+            let fee = if self.dry_run { "Estimated fee" } else { "Fee" };
+            write!(
+                f,
+                "{}: {} SOL over {} transaction(s)",
+                fee,
+                format_sol(self.fee),
+                self.transactions
+            )?;
+        }
+        if self.skipped > 0 {
+            write!(
+                f,
+                "\nSkipped {} account(s) that are frozen or can only be closed by another wallet",
+                self.skipped
+            )?;
+        }
+        if self.left_open > 0 {
+            write!(
+                f,
+                "\n{} {} account(s) left open after a failed transaction",
+                "Stopped:".red().bold(),
+                self.left_open
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A `ClosedAccount` as a CSV record
+#[derive(Serialize)]
+struct ClosedAccountRow<'a> {
+    owner: &'a str,
+    dry_run: bool,
+    account: &'a str,
+    mint: &'a str,
+    program: &'a str,
+    burned: u64,
+    ui_burned: &'a str,
+    rent: u64,
+    signature: Option<&'a str>,
+}
+
+impl CommandOutput for CleanupReport {
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        for account in &self.accounts {
+            writer.serialize(ClosedAccountRow {
+                owner: &self.owner,
+                dry_run: self.dry_run,
+                account: &account.account,
+                mint: &account.mint,
+                program: account.program,
+                burned: account.burned,
+                ui_burned: &account.ui_burned,
+                rent: account.rent,
+                signature: account.signature.as_deref(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl CleanupArgs {
+    pub async fn cleanup_handler(&self, settings: &Settings) -> Result<()> {
+        let rpc_client = get_rpc_client(settings)?;
+        self.cleanup(&rpc_client, settings).await
+    }
+
+    async fn cleanup(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        if self.burn_dust == Some(TokenAmount::All) {
+            anyhow::bail!("`ALL` is not a valid dust amount");
+        }
+        let progress_bar = new_progress_bar(settings.output);
+
+        let owner = settings.keypair_or_default(&self.keypair)?.keypair()?;
+        let owner_address = owner.pubkey().to_string();
+
+        progress_bar.set_message("Looking up token accounts...");
+        let mut closable = Vec::new();
+        let mut skipped = 0;
+        for account in get_token_accounts(rpc_client, &owner.pubkey(), None).await? {
+            let amount = account.amount()?;
+            let dust = self.burn_dust.as_ref().is_some_and(|max| {
+                amount <= max.base_units_floor(account.info.token_amount.decimals)
+            });
+            // Wrapped SOL is unwrapped rather than burned
+            if amount > 0 && (!dust || account.info.is_native) {
+                continue;
+            }
+            let closable_by_owner = account
+                .info
+                .close_authority
+                .as_ref()
+                .is_none_or(|authority| *authority == owner_address);
+            if account.is_frozen() || !closable_by_owner {
+                skipped += 1;
+                continue;
+            }
+            closable.push(Closable::new(&account, amount)?);
+        }
+
+        let burns = closable.iter().filter(|account| account.burn > 0);
+        if !self.dry_run && burns.clone().next().is_some() {
+            let guard = MainnetGuard::new(rpc_client, settings).await?;
+            let mut summary = vec![format!("Owner: {}", owner_address)];
+            for account in burns {
+                summary.push(format!(
+                    "Burn: {} tokens of mint {} in {}",
+                    format_token_amount(account.burn, account.decimals),
+                    account.mint,
+                    account.address
+                ));
+            }
+            summary.push(format!("Closes {} token account(s)", closable.len()));
+            progress_bar.suspend(|| guard.confirm(&summary, self.yes))?;
+        }
+
+        let batches = batches(&closable);
+        let mut accounts = Vec::new();
+        let mut fee = 0;
+        let mut transactions = 0;
+        let mut failure = None;
+        for (i, batch) in batches.iter().enumerate() {
+            let mut instructions = Vec::new();
+            for account in batch.iter() {
+                instructions.extend(account.instructions(&owner.pubkey())?);
+            }
+            let signature = if self.dry_run {
+                progress_bar.set_message("Estimating fees...");
+                let priced = price_token_transaction(
+                    rpc_client,
+                    &self.priority_fee,
+                    &instructions,
+                    &owner.pubkey(),
+                )
+                .await?;
+                fee += priced.fee;
+                None
+            } else {
+                progress_bar.set_position(0);
+                progress_bar.set_message(format!(
+                    "Closing accounts, transaction {} of {}...",
+                    i + 1,
+                    batches.len()
+                ));
+                let result = send_token_transaction(
+                    rpc_client,
+                    &self.priority_fee,
+                    &instructions,
+                    &owner,
+                    &[],
+                    &progress_bar,
+                )
+                .await;
+                // Accounts closed by earlier transactions are still reported
+                let sent = match result {
+                    Ok(sent) => sent,
+                    Err(e) => {
+                        failure = Some((i, e));
+                        break;
+                    }
+                };
+                fee += sent.fee;
+                Some(sent.confirmation.signature.to_string())
+            };
+            transactions += 1;
+            for account in batch.iter() {
+                accounts.push(ClosedAccount {
+                    account: account.address.to_string(),
+                    mint: account.mint.to_string(),
+                    program: program_name(&account.program_id),
+                    burned: account.burn,
+                    ui_burned: format_token_amount(account.burn, account.decimals),
+                    rent: account.rent,
+                    signature: signature.clone(),
+                });
+            }
+        }
+        progress_bar.finish_and_clear();
+
+        let output = CleanupReport {
+            owner: owner_address,
+            dry_run: self.dry_run,
+            reclaimed: accounts.iter().map(|account| account.rent).sum(),
+            left_open: closable.len() - accounts.len(),
+            accounts,
+            skipped,
+            fee,
+            transactions,
+        };
+        print_output(&output, settings.output)?;
+        if let Some((i, e)) = failure {
+            return Err(e.context(format!(
+                "Transaction {} of {} failed, rerun cleanup to close the accounts left open",
+                i + 1,
+                batches.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closable(burn: u64) -> Closable {
+        Closable {
+            address: Pubkey::new_unique(),
+            program_id: spl_token::id(),
+            mint: Pubkey::new_unique(),
+            decimals: 6,
+            rent: 2_039_280,
+            burn,
+        }
+    }
+
+    fn batch_sizes(closable: &[Closable]) -> Vec<usize> {
+        batches(closable).iter().map(|batch| batch.len()).collect()
+    }
+
+    fn instruction_count(batch: &[Closable]) -> usize {
+        let owner = Pubkey::new_unique();
+        batch
+            .iter()
+            .map(|account| account.instructions(&owner).unwrap().len())
+            .sum()
+    }
+
+    #[test]
+    fn nothing_to_close_makes_no_batches() {
+        assert!(batches(&[]).is_empty());
+    }
+
+    #[test]
+    fn empty_accounts_take_one_instruction() {
+        let accounts: Vec<_> = (0..45).map(|_| closable(0)).collect();
+        assert_eq!(batch_sizes(&accounts), vec![20, 20, 5]);
+        let exact: Vec<_> = (0..20).map(|_| closable(0)).collect();
+        assert_eq!(batch_sizes(&exact), vec![20]);
+    }
+
+    #[test]
+    fn dust_accounts_take_two_instructions() {
+        let accounts: Vec<_> = (0..21).map(|_| closable(5)).collect();
+        assert_eq!(batch_sizes(&accounts), vec![10, 10, 1]);
+    }
+
+    #[test]
+    fn mixed_batches_stay_under_the_limit() {
+        // 19 empty accounts leave room for one instruction, not a burn and close
+        let accounts: Vec<_> = (0..19)
+            .map(|_| closable(0))
+            .chain((0..3).map(|_| closable(1)))
+            .collect();
+        assert_eq!(batch_sizes(&accounts), vec![19, 3]);
+        for batch in batches(&accounts) {
+            assert!(instruction_count(batch) <= MAX_INSTRUCTIONS_PER_TRANSACTION);
+        }
+        assert_eq!(instruction_count(&accounts), 19 + 3 * 2);
+    }
+}
@@ -0,0 +1,537 @@
+use crate::args::{Cli, Commands, Network};
+use crate::output::{print_output, CommandOutput, OutputFormat};
+use crate::policy::SpendingPolicy;
+use crate::signer::SignerSource;
+use anyhow::{Context, Ok, Result};
+use clap::{Args, Subcommand, ValueEnum};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const DEFAULT_PROFILE: &str = "default";
+const PROFILE_KEYS: [&str; 7] = [
+    "keypair",
+    "network",
+    "commitment",
+    "output",
+    "max_transfer",
+    "daily_limit",
+    "allowlist",
+];
+
+/// A named set of defaults applied when the matching flags are not given
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Profile {
+    /// Keypair file or signer URI e.g `env:SOLANA_KEYPAIR`
+    pub keypair: Option<String>,
+    /// Network name e.g devnet, or an RPC URL
+    pub network: Option<String>,
+    pub commitment: Option<String>,
+    pub output: Option<String>,
+    /// Most SOL sent to one recipient at once on mainnet e.g 10
+    pub max_transfer: Option<String>,
+    /// Most SOL sent from one wallet over 24 hours on mainnet
+    pub daily_limit: Option<String>,
+    /// Comma separated addresses, the only ones SOL and tokens may be sent to
+    /// on mainnet
+    pub allowlist: Option<String>,
+}
+
+impl Profile {
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        let value = match key {
+            "keypair" => self.keypair.clone(),
+            "network" => self.network.clone(),
+            "commitment" => self.commitment.clone(),
+            "output" => self.output.clone(),
+            "max_transfer" => self.max_transfer.clone(),
+            "daily_limit" => self.daily_limit.clone(),
+            "allowlist" => self.allowlist.clone(),
+            _ => anyhow::bail!(
+                "Unknown config key `{}`, expected one of: {}",
+                key,
+                PROFILE_KEYS.join(", ")
+            ),
+        };
+        Ok(value)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "keypair" => {
+                SignerSource::from_str(value)?;
+                self.keypair = Some(value.to_string());
+            }
+            "network" => {
+                Network::from_str(value)?;
+                self.network = Some(value.to_string());
+            }
+            "commitment" => {
+                CommitmentLevel::from_str(value)
+                    .with_context(|| format!("Invalid commitment level `{}`", value))?;
+                self.commitment = Some(value.to_string());
+            }
+            "output" => {
+                OutputFormat::from_str(value, true).map_err(|_| {
+                    anyhow::anyhow!(
+                        "Invalid output format `{}`, expected one of: human, json, yaml, csv",
+                        value
+                    )
+                })?;
+                self.output = Some(value.to_string());
+            }
+            "max_transfer" => {
+                SpendingPolicy::parse_limit(value)?;
+                self.max_transfer = Some(value.to_string());
+            }
+            "daily_limit" => {
+                SpendingPolicy::parse_limit(value)?;
+                self.daily_limit = Some(value.to_string());
+            }
+            "allowlist" => {
+                SpendingPolicy::parse_allowlist(value)?;
+                self.allowlist = Some(value.to_string());
+            }
+            _ => anyhow::bail!(
+                "Unknown config key `{}`, expected one of: {}",
+                key,
+                PROFILE_KEYS.join(", ")
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// The sol-dash config file, by default `~/.config/sol-dash/config.toml`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Config {
+    #[serde(default = "default_profile_name")]
+    pub active_profile: String,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            active_profile: default_profile_name(),
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_profile_name() -> String {
+    DEFAULT_PROFILE.to_string()
+}
+
+impl Config {
+    pub fn default_path() -> Result<PathBuf> {
+        let home = dirs::home_dir().context("Could not find your home directory")?;
+        Ok(home.join(".config").join("sol-dash").join("config.toml"))
+    }
+
+    /// Loads the config file, returning `None` when it does not exist yet
+    pub fn load(path: &Path) -> Result<Option<Config>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file `{}`", path.display()))?;
+        let config = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file `{}`", path.display()))?;
+        Ok(Some(config))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("Failed to create config directory `{}`", parent.display())
+            })?;
+        }
+        let contents = toml::to_string_pretty(self)?;
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write config file `{}`", path.display()))?;
+        Ok(())
+    }
+}
+
+/// The subset of the Solana CLI `config.yml` that sol-dash understands
+#[derive(Deserialize, Default, Debug)]
+struct SolanaCliConfig {
+    #[serde(default)]
+    json_rpc_url: Option<String>,
+    #[serde(default)]
+    keypair_path: Option<PathBuf>,
+    #[serde(default)]
+    commitment: Option<String>,
+}
+
+impl SolanaCliConfig {
+    fn path() -> Option<PathBuf> {
+        let home = dirs::home_dir()?;
+        Some(
+            home.join(".config")
+                .join("solana")
+                .join("cli")
+                .join("config.yml"),
+        )
+    }
+
+    fn load(path: &Path) -> Result<Profile> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read Solana CLI config `{}`", path.display()))?;
+        let config: SolanaCliConfig = serde_yaml::from_str(&contents)
+            .with_context(|| format!("Failed to parse Solana CLI config `{}`", path.display()))?;
+        Ok(Profile {
+            keypair: config.keypair_path.map(|path| path.display().to_string()),
+            network: config.json_rpc_url,
+            commitment: config.commitment,
+            ..Profile::default()
+        })
+    }
+}
+
+/// The config file and the active profile as written, none of the profile's
+/// values are validated yet so `config` can still repair a bad one
+#[derive(Clone, Debug)]
+pub struct LoadedProfile {
+    pub config_file: PathBuf,
+    pub config: Option<Config>,
+    pub name: String,
+    pub profile: Profile,
+    /// The Solana CLI config the profile falls back to without a config file
+    pub solana_cli_config: Option<PathBuf>,
+}
+
+impl LoadedProfile {
+    pub fn load(cli: &Cli) -> Result<LoadedProfile> {
+        let config_file = match &cli.config {
+            Some(path) => path.clone(),
+            None => Config::default_path()?,
+        };
+        let config = Config::load(&config_file)?;
+        let (name, profile, solana_cli_config) = match &config {
+            Some(config) => {
+                let name = cli
+                    .profile
+                    .clone()
+                    .unwrap_or_else(|| config.active_profile.clone());
+                let profile = match config.profiles.get(&name) {
+                    Some(profile) => profile.clone(),
+                    // `config set` and `config use-profile` are how new profiles get created
+                    None if cli.profile.is_some()
+                        && !matches!(cli.command, Commands::Config(_)) =>
+                    {
+                        anyhow::bail!("Profile `{}` does not exist in the config file", name)
+                    }
+                    None => Profile::default(),
+                };
+                (name, profile, None)
+            }
+            None => {
+                let name = cli.profile.clone().unwrap_or_else(default_profile_name);
+                match SolanaCliConfig::path().filter(|path| path.exists()) {
+                    Some(path) => (name, SolanaCliConfig::load(&path)?, Some(path)),
+                    None => (name, Profile::default(), None),
+                }
+            }
+        };
+        Ok(LoadedProfile {
+            config_file,
+            config,
+            name,
+            profile,
+            solana_cli_config,
+        })
+    }
+
+    /// The output format of the flag or the profile, a bad one in the
+    /// profile falls back to the default instead of failing
+    pub fn output_or_default(&self, cli: &Cli) -> OutputFormat {
+        cli.output
+            .or_else(|| {
+                let output = self.profile.output.as_deref()?;
+                OutputFormat::from_str(output, true).ok()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Command line flags resolved against the active profile
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub config_file: PathBuf,
+    pub network: Network,
+    pub headers: Vec<String>,
+    pub commitment: CommitmentConfig,
+    pub keypair: Option<SignerSource>,
+    pub output: OutputFormat,
+    pub policy: SpendingPolicy,
+}
+
+impl Settings {
+    pub fn load(cli: &Cli) -> Result<Settings> {
+        let LoadedProfile {
+            config_file,
+            name: profile_name,
+            profile,
+            ..
+        } = LoadedProfile::load(cli)?;
+
+        let network = match (&cli.cluster.url, &cli.cluster.network, &profile.network) {
+            (Some(url), _, _) => Network::Custom(url.clone()),
+            (None, Some(network), _) => network.clone(),
+            (None, None, Some(network)) => Network::from_str(network)
+                .with_context(|| format!("Invalid network in profile `{}`", profile_name))?,
+            (None, None, None) => Network::default(),
+        };
+        let commitment = match (&cli.cluster.commitment, &profile.commitment) {
+            (Some(commitment), _) => *commitment,
+            (None, Some(commitment)) => CommitmentLevel::from_str(commitment)
+                .with_context(|| format!("Invalid commitment in profile `{}`", profile_name))?,
+            (None, None) => CommitmentLevel::default(),
+        };
+        let output = match (&cli.output, &profile.output) {
+            (Some(output), _) => *output,
+            (None, Some(output)) => OutputFormat::from_str(output, true).map_err(|_| {
+                anyhow::anyhow!("Invalid output format in profile `{}`", profile_name)
+            })?,
+            (None, None) => OutputFormat::default(),
+        };
+        let keypair = profile
+            .keypair
+            .as_deref()
+            .map(SignerSource::from_str)
+            .transpose()
+            .with_context(|| format!("Invalid keypair in profile `{}`", profile_name))?;
+        let policy = SpendingPolicy {
+            max_transfer: profile
+                .max_transfer
+                .as_deref()
+                .map(SpendingPolicy::parse_limit)
+                .transpose()
+                .with_context(|| format!("Invalid max_transfer in profile `{}`", profile_name))?,
+            daily_limit: profile
+                .daily_limit
+                .as_deref()
+                .map(SpendingPolicy::parse_limit)
+                .transpose()
+                .with_context(|| format!("Invalid daily_limit in profile `{}`", profile_name))?,
+            allowlist: profile
+                .allowlist
+                .as_deref()
+                .map(SpendingPolicy::parse_allowlist)
+                .transpose()
+                .with_context(|| format!("Invalid allowlist in profile `{}`", profile_name))?,
+        };
+
+        Ok(Settings {
+            config_file,
+            network,
+            headers: cli.cluster.headers.clone(),
+            commitment: CommitmentConfig { commitment },
+            keypair,
+            output,
+            policy,
+        })
+    }
+
+    /// The keypair given on the command line, falling back to the profile's default
+    pub fn keypair_or_default(&self, keypair: &Option<SignerSource>) -> Result<SignerSource> {
+        keypair.clone().or_else(|| self.keypair.clone()).context(
+            "No keypair provided, pass one explicitly or set a default with `sol-dash config set keypair <path>`",
+        )
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommands,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum ConfigCommands {
+    /// Show the settings of the current profile, or a single key
+    Get {
+        /// One of keypair, network, commitment, output, max_transfer,
+        /// daily_limit, allowlist
+        key: Option<String>,
+    },
+    /// Set a key in the current profile, creating the profile if needed
+    Set {
+        /// One of keypair, network, commitment, output, max_transfer,
+        /// daily_limit, allowlist
+        key: String,
+        value: String,
+    },
+    /// Make a profile the active one
+    UseProfile {
+        /// Name of the profile
+        name: String,
+    },
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConfigEntry {
+    pub key: String,
+    /// `None` when the key is not set in the profile
+    pub value: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConfigValues {
+    pub config_file: String,
+    pub profile: String,
+    /// The Solana CLI config the values come from when there is no config
+    /// file yet
+    pub solana_cli_config: Option<String>,
+    pub settings: Vec<ConfigEntry>,
+}
+
+impl fmt::Display for ConfigValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Config file: {}", self.config_file.green())?;
+        write!(f, "Profile: {}", self.profile.blue().bold())?;
+        if let Some(path) = &self.solana_cli_config {
+            write!(
+                f,
+                "\nFalling back to the Solana CLI config: {}",
+                path.green()
+            )?;
+        }
+        for entry in &self.settings {
+            write!(
+                f,
+                "\n{}: {}",
+                entry.key,
+                entry.value.as_deref().unwrap_or("(not set)").bold()
+            )?;
+        }
+        fmt::Result::Ok(())
+    }
+}
+
+/// A `ConfigEntry` as a CSV record
+#[derive(Serialize)]
+struct ConfigEntryRow<'a> {
+    config_file: &'a str,
+    profile: &'a str,
+    solana_cli_config: Option<&'a str>,
+    key: &'a str,
+    value: Option<&'a str>,
+}
+
+impl CommandOutput for ConfigValues {
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        for entry in &self.settings {
+            writer.serialize(ConfigEntryRow {
+                config_file: &self.config_file,
+                profile: &self.profile,
+                solana_cli_config: self.solana_cli_config.as_deref(),
+                key: &entry.key,
+                value: entry.value.as_deref(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConfigKeySet {
+    pub config_file: String,
+    pub profile: String,
+    pub key: String,
+    pub value: String,
+}
+
+impl fmt::Display for ConfigKeySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Set {} to {} in profile {}",
+            self.key,
+            self.value.green().bold(),
+            self.profile.blue().bold()
+        )
+    }
+}
+
+impl CommandOutput for ConfigKeySet {}
+
+#[derive(Serialize, Debug)]
+pub struct ProfileActivated {
+    pub config_file: String,
+    pub profile: String,
+}
+
+impl fmt::Display for ProfileActivated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Active profile is now {}", self.profile.blue().bold())
+    }
+}
+
+impl CommandOutput for ProfileActivated {}
+
+impl ConfigArgs {
+    pub fn config_handler(&self, loaded: &LoadedProfile, format: OutputFormat) -> Result<()> {
+        let path = &loaded.config_file;
+        let config_file = path.display().to_string();
+        let mut config = loaded.config.clone().unwrap_or_default();
+        match &self.command {
+            ConfigCommands::Get { key } => {
+                let keys = match key {
+                    Some(key) => vec![key.as_str()],
+                    None => PROFILE_KEYS.to_vec(),
+                };
+                let mut entries = Vec::new();
+                for key in keys {
+                    entries.push(ConfigEntry {
+                        key: key.to_string(),
+                        value: loaded.profile.get(key)?,
+                    });
+                }
+                let output = ConfigValues {
+                    config_file,
+                    profile: loaded.name.clone(),
+                    solana_cli_config: loaded
+                        .solana_cli_config
+                        .as_ref()
+                        .map(|path| path.display().to_string()),
+                    settings: entries,
+                };
+                print_output(&output, format)?;
+            }
+            ConfigCommands::Set { key, value } => {
+                config
+                    .profiles
+                    .entry(loaded.name.clone())
+                    .or_default()
+                    .set(key, value)?;
+                config.save(path)?;
+                let output = ConfigKeySet {
+                    config_file,
+                    profile: loaded.name.clone(),
+                    key: key.clone(),
+                    value: value.clone(),
+                };
+                print_output(&output, format)?;
+            }
+            ConfigCommands::UseProfile { name } => {
+                config.profiles.entry(name.clone()).or_default();
+                config.active_profile = name.clone();
+                config.save(path)?;
+                let output = ProfileActivated {
+                    config_file,
+                    profile: name.clone(),
+                };
+                print_output(&output, format)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+use crate::config::Settings;
+use crate::output::{print_outputs, CommandOutput, OutputFormat};
+use anyhow::{Context, Result};
+use clap::Args;
+use colored::*;
+use indicatif::{HumanCount, HumanDuration, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde::Serialize;
+use solana_sdk::signature::{write_keypair_file, Keypair};
+use solana_sdk::signer::Signer;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+/// Keypairs generated by a thread before it publishes its attempt count
+const BATCH_SIZE: u64 = 1_000;
+
+#[derive(Args, Clone, Debug)]
+pub struct GrindArgs {
+    /// Characters the wallet address must start with
+    #[arg(short = 's', long, required_unless_present = "ends_with")]
+    pub starts_with: Option<String>,
+    /// Characters the wallet address must end with
+    #[arg(short = 'e', long)]
+    pub ends_with: Option<String>,
+    /// Match the characters regardless of their case
+    #[arg(short = 'i', long)]
+    pub ignore_case: bool,
+    /// How many matching keypairs to find
+    #[arg(short = 'c', long, default_value_t = 1)]
+    pub count: usize,
+    /// Number of threads to search with, defaults to every CPU core
+    #[arg(short = 't', long)]
+    pub threads: Option<usize>,
+    /// Directory where matching keypairs are saved as `<address>.json`
+    #[arg(short = 'o', long, default_value = ".")]
+    pub output_dir: PathBuf,
+}
+
+#[derive(Serialize, Debug)]
+pub struct VanityKeypair {
+    pub address: String,
+    pub output_file: String,
+    /// Keypairs generated across all threads when this one was found
+    pub attempts: u64,
+    pub seconds: f64,
+}
+
+impl fmt::Display for VanityKeypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Found {} after {} attempts, saved to {}",
+            self.address.blue().bold(),
+            HumanCount(self.attempts),
+            self.output_file.green().bold()
+        )
+    }
+}
+
+impl CommandOutput for VanityKeypair {}
+
+/// The address pattern searched for
+struct Pattern {
+    prefix: String,
+    suffix: String,
+    ignore_case: bool,
+}
+
+impl Pattern {
+    fn matches(&self, address: &str) -> bool {
+        if self.ignore_case {
+            let address = address.to_lowercase();
+            address.starts_with(&self.prefix) && address.ends_with(&self.suffix)
+        } else {
+            address.starts_with(&self.prefix) && address.ends_with(&self.suffix)
+        }
+    }
+
+    /// Expected number of keypairs to generate per match
+    fn expected_attempts(&self) -> f64 {
+        self.prefix
+            .chars()
+            .chain(self.suffix.chars())
+            .map(|c| 58.0 / base58_variants(c, self.ignore_case) as f64)
+            .product()
+    }
+}
+
+/// How many base58 characters match `c`
+fn base58_variants(c: char, ignore_case: bool) -> usize {
+    if !ignore_case {
+        return BASE58_ALPHABET.contains(c) as usize;
+    }
+    BASE58_ALPHABET
+        .chars()
+        .filter(|a| a.eq_ignore_ascii_case(&c))
+        .count()
+}
+
+fn validate(pattern: &str, ignore_case: bool) -> Result<()> {
+    for c in pattern.chars() {
+        if base58_variants(c, ignore_case) == 0 {
+            anyhow::bail!(
+                "`{}` in `{}` can never appear in an address, base58 excludes 0, O, I and l",
+                c,
+                pattern
+            );
+        }
+    }
+    Ok(())
+}
+
+impl GrindArgs {
+    pub fn grind_handler(&self, settings: &Settings) -> Result<()> {
+        let prefix = self.starts_with.clone().unwrap_or_default();
+        let suffix = self.ends_with.clone().unwrap_or_default();
+        validate(&prefix, self.ignore_case)?;
+        validate(&suffix, self.ignore_case)?;
+        if self.count == 0 {
+            anyhow::bail!("`count` must be at least 1");
+        }
+        let pattern = Pattern {
+            prefix: if self.ignore_case {
+                prefix.to_lowercase()
+            } else {
+                prefix
+            },
+            suffix: if self.ignore_case {
+                suffix.to_lowercase()
+            } else {
+                suffix
+            },
+            ignore_case: self.ignore_case,
+        };
+        let threads = match self.threads {
+            Some(0) => anyhow::bail!("`threads` must be at least 1"),
+            Some(threads) => threads,
+            None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+        std::fs::create_dir_all(&self.output_dir).with_context(|| {
+            format!(
+                "Failed to create output directory `{}`",
+                self.output_dir.display()
+            )
+        })?;
+
+        let progress_bar = ProgressBar::new_spinner();
+        progress_bar.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.green} [{elapsed_precise}] {msg}")
+                .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+        );
+        if settings.output != OutputFormat::Human {
+            progress_bar.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        progress_bar.enable_steady_tick(Duration::from_millis(100));
+
+        let attempts = AtomicU64::new(0);
+        let done = AtomicBool::new(false);
+        let found = Mutex::new(Vec::new());
+        let failed = Mutex::new(None);
+        let start = Instant::now();
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| self.search(&pattern, &attempts, &done, &found, &failed, start));
+            }
+            let expected = pattern.expected_attempts() * self.count as f64;
+            while !done.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(250));
+                let tried = attempts.load(Ordering::Relaxed);
+                let rate = tried as f64 / start.elapsed().as_secs_f64().max(0.001);
+                let remaining = (expected - tried as f64).max(0.0);
+                progress_bar.set_message(format!(
+                    "{} attempts, {}/s, found {} of {}, ETA ~{}",
+                    HumanCount(tried),
+                    HumanCount(rate as u64),
+                    found.lock().map_or(0, |f| f.len()),
+                    self.count,
+                    HumanDuration(Duration::from_secs_f64(remaining / rate.max(1.0)))
+                ));
+            }
+        });
+        progress_bar.finish_and_clear();
+
+        let poisoned = || anyhow::anyhow!("A search thread panicked");
+        let results = found.into_inner().map_err(|_| poisoned())?;
+        let failed = failed.into_inner().map_err(|_| poisoned())?;
+        // Keypairs saved before a write failed are still reported
+        if !results.is_empty() {
+            print_outputs(results, settings.output)?;
+        }
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Writes a matching keypair to `<address>.json` in the output directory
+    fn save(&self, keypair: &Keypair) -> Result<(String, String)> {
+        let address = keypair.pubkey().to_string();
+        let output_file = self.output_dir.join(format!("{}.json", address));
+        write_keypair_file(keypair, &output_file).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to write keypair to file `{}`: {}",
+                output_file.display(),
+                e
+            )
+        })?;
+        Ok((address, output_file.display().to_string()))
+    }
+
+    /// Generates keypairs until `count` matches are found, saving each match
+    /// as soon as it is found so an interrupted search keeps them
+    fn search(
+        &self,
+        pattern: &Pattern,
+        attempts: &AtomicU64,
+        done: &AtomicBool,
+        found: &Mutex<Vec<VanityKeypair>>,
+        failed: &Mutex<Option<anyhow::Error>>,
+        start: Instant,
+    ) {
+        while !done.load(Ordering::Relaxed) {
+            for _ in 0..BATCH_SIZE {
+                let keypair = Keypair::new();
+                if !pattern.matches(&keypair.pubkey().to_string()) {
+                    continue;
+                }
+                let Ok(mut found) = found.lock() else {
+                    return;
+                };
+                if found.len() < self.count && !done.load(Ordering::Relaxed) {
+                    match self.save(&keypair) {
+                        Ok((address, output_file)) => found.push(VanityKeypair {
+                            address,
+                            output_file,
+                            attempts: attempts.load(Ordering::Relaxed),
+                            seconds: start.elapsed().as_secs_f64(),
+                        }),
+                        Err(e) => {
+                            if let Ok(mut failed) = failed.lock() {
+                                *failed = Some(e);
+                            }
+                            done.store(true, Ordering::Relaxed);
+                            return;
+                        }
+                    }
+                }
+                if found.len() >= self.count {
+                    done.store(true, Ordering::Relaxed);
+                    return;
+                }
+            }
+            attempts.fetch_add(BATCH_SIZE, Ordering::Relaxed);
+        }
+    }
+}
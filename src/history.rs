@@ -0,0 +1,271 @@
+use crate::amount::format_sol;
+use crate::args::get_rpc_client;
+use crate::config::Settings;
+use crate::output::{print_output, CommandOutput};
+use crate::sender::new_progress_bar;
+use crate::signer::SignerSource;
+use crate::token::parse_pubkey;
+use crate::transaction::{
+    fetch_transaction, format_block_time, format_delta, history_commitment, ParsedTransaction,
+};
+use anyhow::Result;
+use clap::{ArgGroup, Args};
+use colored::*;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::signer::Signer;
+use std::fmt;
+use std::str::FromStr;
+
+/// Most signatures `getSignaturesForAddress` returns per request
+const MAX_SIGNATURES_PER_REQUEST: usize = 1000;
+
+/// How many transactions are fetched at once
+const CONCURRENT_FETCHES: usize = 8;
+
+#[derive(Args, Clone, Debug)]
+#[command(group(ArgGroup::new("wallet").args(["address", "keypair"])))]
+pub struct HistoryArgs {
+    /// The public key of the wallet
+    #[arg(short = 'a', long)]
+    pub address: Option<String>,
+    /// The keypair file or signer URI e.g stdin:, prompt: or env:VAR, defaults
+    /// to the keypair in your config profile
+    #[arg(short = 'k', long)]
+    pub keypair: Option<SignerSource>,
+    /// How many transactions to list, newest first
+    #[arg(short, long, default_value_t = 20, value_parser = clap::value_parser!(u32).range(1..))]
+    pub limit: u32,
+    /// Only list transactions older than this signature, to page back
+    #[arg(long)]
+    pub before: Option<Signature>,
+    /// Only list transactions newer than this signature
+    #[arg(long)]
+    pub until: Option<Signature>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct HistoryEntry {
+    pub signature: String,
+    pub slot: u64,
+    /// Unix timestamp of the block, unset when the node does not know it
+    pub block_time: Option<i64>,
+    pub time: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+    /// Fee in lamports, unset when the transaction could not be fetched
+    pub fee: Option<u64>,
+    /// Lamports the wallet gained, or lost when negative, fee included
+    pub lamports_delta: Option<i64>,
+    pub counterparty: Option<String>,
+    pub instructions: Vec<String>,
+    pub memos: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TransactionHistory {
+    pub address: String,
+    pub transactions: Vec<HistoryEntry>,
+    /// Pass as `--before` to list the next, older page
+    pub next_before: Option<String>,
+}
+
+impl fmt::Display for TransactionHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.transactions.is_empty() {
+            return write!(f, "No transactions found for {}", self.address);
+        }
+        write!(
+            f,
+            "{} transaction(s) of {}, newest first",
+            self.transactions.len(),
+            self.address.blue()
+        )?;
+        for entry in &self.transactions {
+            let time = entry.time.as_deref().unwrap_or("unknown time");
+            let status = if entry.success {
+                "success".green()
+            } else {
+                "failed".red().bold()
+            };
+            write!(f, "\n\n{}  {}", time.bold(), status)?;
+            if let Some(delta) = entry.lamports_delta {
+                write!(f, "  {}", format_delta(delta))?;
+            }
+            write!(f, "\n  Signature: {}", entry.signature.yellow())?;
+            if let Some(error) = &entry.error {
+                write!(f, "\n  Error: {}", error)?;
+            }
+            if let Some(counterparty) = &entry.counterparty {
+                write!(f, "\n  Counterparty: {}", counterparty)?;
+            }
+            if !entry.instructions.is_empty() {
+                write!(f, "\n  Instructions: {}", entry.instructions.join(", "))?;
+            }
+            for memo in &entry.memos {
+                write!(f, "\n  Memo: {}", memo)?;
+            }
+            if let Some(fee) = entry.fee {
+                write!(f, "\n  Fee: {} SOL", format_sol(fee))?;
+            }
+        }
+        if let Some(before) = &self.next_before {
+            write!(f, "\n\nOlder transactions: pass `--before {}`", before)?;
+        }
+        Ok(())
+    }
+}
+
+/// A `HistoryEntry` as a CSV record, with lists joined by `; `
+#[derive(Serialize)]
+struct HistoryRow<'a> {
+    address: &'a str,
+    time: Option<&'a str>,
+    block_time: Option<i64>,
+    slot: u64,
+    signature: &'a str,
+    success: bool,
+    error: Option<&'a str>,
+    fee: Option<u64>,
+    lamports_delta: Option<i64>,
+    counterparty: Option<&'a str>,
+    instructions: String,
+    memos: String,
+}
+
+impl CommandOutput for TransactionHistory {
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        for entry in &self.transactions {
+            writer.serialize(HistoryRow {
+                address: &self.address,
+                time: entry.time.as_deref(),
+                block_time: entry.block_time,
+                slot: entry.slot,
+                signature: &entry.signature,
+                success: entry.success,
+                error: entry.error.as_deref(),
+                fee: entry.fee,
+                lamports_delta: entry.lamports_delta,
+                counterparty: entry.counterparty.as_deref(),
+                instructions: entry.instructions.join("; "),
+                memos: entry.memos.join("; "),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl HistoryArgs {
+    pub async fn history_handler(&self, settings: &Settings) -> Result<()> {
+        let rpc_client = get_rpc_client(settings)?;
+        self.history(&rpc_client, settings).await
+    }
+
+    fn wallet(&self, settings: &Settings) -> Result<Pubkey> {
+        match &self.address {
+            Some(address) => parse_pubkey(address, "wallet"),
+            None => Ok(settings
+                .keypair_or_default(&self.keypair)?
+                .keypair()?
+                .pubkey()),
+        }
+    }
+
+    async fn history(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        let progress_bar = new_progress_bar(settings.output);
+        let address = self.wallet(settings)?;
+
+        progress_bar.set_message("Fetching signatures...");
+        let limit = self.limit as usize;
+        let mut statuses = Vec::new();
+        let mut before = self.before;
+        while statuses.len() < limit {
+            let page_size = (limit - statuses.len()).min(MAX_SIGNATURES_PER_REQUEST);
+            let config = GetConfirmedSignaturesForAddress2Config {
+                before,
+                until: self.until,
+                limit: Some(page_size),
+                commitment: Some(history_commitment(rpc_client)),
+            };
+            let page = rpc_client
+                .get_signatures_for_address_with_config(&address, config)
+                .await?;
+            let last_page = page.len() < page_size;
+            statuses.extend(page);
+            match statuses.last() {
+                Some(last) if !last_page => before = Some(Signature::from_str(&last.signature)?),
+                _ => break,
+            }
+        }
+
+        let total = statuses.len();
+        let fetched = std::cell::Cell::new(0);
+        let transactions = stream::iter(&statuses)
+            .map(|status| async {
+                let transaction = match Signature::from_str(&status.signature) {
+                    Ok(signature) => fetch_transaction(rpc_client, &signature).await.ok(),
+                    Err(_) => None,
+                };
+                fetched.set(fetched.get() + 1);
+                progress_bar.set_position((fetched.get() * 100 / total) as u64);
+                progress_bar.set_message(format!(
+                    "Fetched {} of {} transactions",
+                    fetched.get(),
+                    total
+                ));
+                transaction
+            })
+            .buffered(CONCURRENT_FETCHES)
+            .collect::<Vec<_>>()
+            .await;
+        progress_bar.finish_and_clear();
+
+        let wallet = address.to_string();
+        let mut entries = Vec::new();
+        for (status, transaction) in statuses.iter().zip(&transactions) {
+            let mut entry = HistoryEntry {
+                signature: status.signature.clone(),
+                slot: status.slot,
+                block_time: status.block_time,
+                time: status.block_time.map(format_block_time),
+                success: status.err.is_none(),
+                error: status.err.as_ref().map(|err| err.to_string()),
+                fee: None,
+                lamports_delta: None,
+                counterparty: None,
+                instructions: Vec::new(),
+                // Only the raw `[length] text` form is known without the transaction
+                memos: status.memo.iter().cloned().collect(),
+            };
+            // A node that pruned the transaction still lists its signature
+            if let Some(parsed) = transaction
+                .as_ref()
+                .and_then(|transaction| ParsedTransaction::new(transaction).ok())
+            {
+                if let Some(err) = &parsed.meta.err {
+                    entry.error = Some(parsed.explain_error(err));
+                }
+                entry.fee = Some(parsed.meta.fee);
+                entry.lamports_delta = Some(parsed.lamports_delta(&wallet));
+                entry.counterparty = parsed.counterparty(&wallet);
+                entry.instructions = parsed.instruction_summary();
+                entry.memos = parsed.memos();
+            }
+            entries.push(entry);
+        }
+
+        let output = TransactionHistory {
+            address: wallet,
+            next_before: (entries.len() == limit)
+                .then(|| entries.last().map(|entry| entry.signature.clone()))
+                .flatten(),
+            transactions: entries,
+        };
+        print_output(&output, settings.output)?;
+        Ok(())
+    }
+}
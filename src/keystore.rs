@@ -0,0 +1,500 @@
+use crate::args::check_overwrite;
+use crate::config::Settings;
+use crate::mnemonic::read_secret;
+use crate::output::{print_output, CommandOutput};
+use anyhow::{Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use clap::{Args, ValueEnum};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Passphrase used for encrypted keypair files instead of prompting
+pub const PASSPHRASE_ENV: &str = "SOL_DASH_KEYPAIR_PASSPHRASE";
+/// File the passphrase is read from instead of prompting, `/dev/fd/<n>`
+/// reads it from an inherited file descriptor
+pub const PASSPHRASE_FILE_ENV: &str = "SOL_DASH_KEYPAIR_PASSPHRASE_FILE";
+
+const KEYSTORE_VERSION: u8 = 1;
+const KDF_NAME: &str = "argon2id";
+const CIPHER_NAME: &str = "xchacha20poly1305";
+/// Argon2id with 64 MiB and 3 passes, roughly half a second on a laptop
+const ARGON2_MEMORY_KIB: u32 = 64 * 1024;
+const ARGON2_ITERATIONS: u32 = 3;
+const ARGON2_PARALLELISM: u32 = 1;
+/// Most a keystore may ask for, so a crafted file cannot exhaust the memory
+const MAX_ARGON2_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ARGON2_ITERATIONS: u32 = 32;
+const MAX_ARGON2_PARALLELISM: u32 = 16;
+const SALT_LEN: usize = 16;
+
+/// A keypair file encrypted with a key derived from a passphrase. The
+/// address is kept in the clear so the file can be identified without the
+/// passphrase, it is also authenticated along with the secret key.
+#[derive(Serialize, Deserialize, Debug)]
+struct Keystore {
+    version: u8,
+    pubkey: String,
+    kdf: Kdf,
+    cipher: Cipher,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Kdf {
+    name: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    salt: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Cipher {
+    name: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl Kdf {
+    fn derive_key(&self, passphrase: &str) -> Result<[u8; 32]> {
+        if self.name != KDF_NAME {
+            anyhow::bail!("Unsupported key derivation function `{}`", self.name);
+        }
+        if self.memory_kib > MAX_ARGON2_MEMORY_KIB
+            || self.iterations > MAX_ARGON2_ITERATIONS
+            || self.parallelism > MAX_ARGON2_PARALLELISM
+        {
+            anyhow::bail!(
+                "Key derivation parameters exceed the supported maximum of {} KiB, {} iterations and {} lanes",
+                MAX_ARGON2_MEMORY_KIB,
+                MAX_ARGON2_ITERATIONS,
+                MAX_ARGON2_PARALLELISM
+            );
+        }
+        let salt = BASE64
+            .decode(&self.salt)
+            .context("Invalid salt in keystore")?;
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| anyhow::anyhow!("Invalid key derivation parameters: {}", e))?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| anyhow::anyhow!("Failed to derive the encryption key: {}", e))?;
+        Ok(key)
+    }
+}
+
+impl Keystore {
+    fn seal(keypair: &Keypair, passphrase: &str) -> Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let kdf = Kdf {
+            name: KDF_NAME.to_string(),
+            memory_kib: ARGON2_MEMORY_KIB,
+            iterations: ARGON2_ITERATIONS,
+            parallelism: ARGON2_PARALLELISM,
+            salt: BASE64.encode(salt),
+        };
+        let key = kdf.derive_key(passphrase)?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let pubkey = keypair.pubkey();
+        let ciphertext = XChaCha20Poly1305::new(&key.into())
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &keypair.to_bytes(),
+                    aad: pubkey.as_ref(),
+                },
+            )
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt the keypair"))?;
+        Ok(Self {
+            version: KEYSTORE_VERSION,
+            pubkey: pubkey.to_string(),
+            kdf,
+            cipher: Cipher {
+                name: CIPHER_NAME.to_string(),
+                nonce: BASE64.encode(nonce),
+                ciphertext: BASE64.encode(ciphertext),
+            },
+        })
+    }
+
+    fn open(&self, passphrase: &str) -> Result<Keypair> {
+        if self.version != KEYSTORE_VERSION {
+            anyhow::bail!("Unsupported keystore version {}", self.version);
+        }
+        if self.cipher.name != CIPHER_NAME {
+            anyhow::bail!("Unsupported cipher `{}`", self.cipher.name);
+        }
+        let pubkey = self
+            .pubkey
+            .parse::<solana_sdk::pubkey::Pubkey>()
+            .context("Invalid public key in keystore")?;
+        let nonce = BASE64
+            .decode(&self.cipher.nonce)
+            .context("Invalid nonce in keystore")?;
+        if nonce.len() != 24 {
+            anyhow::bail!("Invalid nonce in keystore");
+        }
+        let ciphertext = BASE64
+            .decode(&self.cipher.ciphertext)
+            .context("Invalid ciphertext in keystore")?;
+        let key = self.kdf.derive_key(passphrase)?;
+        let secret = XChaCha20Poly1305::new(&key.into())
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: pubkey.as_ref(),
+                },
+            )
+            .map_err(|_| anyhow::anyhow!("Wrong passphrase or corrupted keystore"))?;
+        let keypair = Keypair::from_bytes(&secret)
+            .map_err(|e| anyhow::anyhow!("Invalid keypair in keystore: {}", e))?;
+        if keypair.pubkey() != pubkey {
+            anyhow::bail!("The decrypted keypair does not match the keystore address");
+        }
+        Ok(keypair)
+    }
+}
+
+/// Plain keypair files are a JSON array, keystores a JSON object
+fn is_keystore(contents: &str) -> bool {
+    contents.trim_start().starts_with('{')
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read keypair from file `{}`", path.display()))
+}
+
+fn parse_plain_keypair(contents: &str, path: &Path) -> Result<Keypair> {
+    solana_sdk::signature::read_keypair(&mut contents.as_bytes()).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to read keypair from file `{}`: {}",
+            path.display(),
+            e
+        )
+    })
+}
+
+/// The passphrase from the environment, if one was provided there
+fn passphrase_from_env() -> Result<Option<String>> {
+    if let Some(passphrase) = std::env::var_os(PASSPHRASE_ENV) {
+        return Ok(Some(passphrase.to_string_lossy().into_owned()));
+    }
+    let Some(file) = std::env::var_os(PASSPHRASE_FILE_ENV) else {
+        return Ok(None);
+    };
+    let passphrase = std::fs::read_to_string(&file).with_context(|| {
+        format!(
+            "Failed to read the passphrase from `{}`",
+            Path::new(&file).display()
+        )
+    })?;
+    Ok(Some(passphrase.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// Asks for a new keystore passphrase, twice on a terminal
+fn new_passphrase() -> Result<String> {
+    let passphrase = match passphrase_from_env()? {
+        Some(passphrase) => passphrase,
+        None => {
+            let passphrase = read_secret("Keypair file passphrase: ")?;
+            if std::io::stdin().is_terminal() {
+                let confirmation = read_secret("Confirm keypair file passphrase: ")?;
+                if passphrase != confirmation {
+                    anyhow::bail!("Passphrases do not match");
+                }
+            }
+            passphrase
+        }
+    };
+    if passphrase.is_empty() {
+        anyhow::bail!("The keypair file passphrase can not be empty");
+    }
+    Ok(passphrase)
+}
+
+fn existing_passphrase(path: &Path) -> Result<String> {
+    match passphrase_from_env()? {
+        Some(passphrase) => Ok(passphrase),
+        None => read_secret(&format!("Passphrase for {}: ", path.display())),
+    }
+}
+
+/// Reads a plain or encrypted keypair file, asking for the passphrase of
+/// encrypted ones
+pub fn read_keypair(path: &Path) -> Result<Keypair> {
+    let contents = read_file(path)?;
+    if !is_keystore(&contents) {
+        return parse_plain_keypair(&contents, path);
+    }
+    let keystore: Keystore = serde_json::from_str(&contents)
+        .with_context(|| format!("Invalid encrypted keypair file `{}`", path.display()))?;
+    let passphrase = existing_passphrase(path)?;
+    keystore
+        .open(&passphrase)
+        .with_context(|| format!("Failed to decrypt keypair file `{}`", path.display()))
+}
+
+/// Asks for a new passphrase and writes `keypair` encrypted with it
+pub fn write_encrypted_keypair(keypair: &Keypair, path: &Path) -> Result<()> {
+    let passphrase = new_passphrase()?;
+    let keystore = Keystore::seal(keypair, &passphrase)?;
+    write_private_file(path, serde_json::to_string_pretty(&keystore)?.as_bytes())
+}
+
+/// Writes `keypair` as the JSON byte array the Solana tools use
+pub fn write_plain_keypair(keypair: &Keypair, path: &Path) -> Result<()> {
+    let bytes = serde_json::to_string(&keypair.to_bytes().to_vec())?;
+    write_private_file(path, bytes.as_bytes())
+}
+
+/// Writes through a temporary file in the same directory, so replacing a
+/// keypair file in place never leaves it half written
+fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("`{}` is not a file path", path.display()))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let write = || -> Result<()> {
+        // The mode only applies to a new file, a stale one may be readable by others
+        if tmp_path.exists() {
+            std::fs::remove_file(&tmp_path)?;
+        }
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    };
+    write().map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        anyhow::anyhow!(
+            "Failed to write keypair to file `{}`: {}",
+            path.display(),
+            e
+        )
+    })
+}
+
+/// How a secret key is printed when it is explicitly exported
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum SecretFormat {
+    /// The format wallets like Phantom import
+    Base58,
+    /// The format of Solana CLI keypair files
+    #[default]
+    JsonArray,
+    /// Hex-encoded bytes
+    Bytes,
+}
+
+impl SecretFormat {
+    pub fn export(&self, keypair: &Keypair) -> String {
+        match self {
+            SecretFormat::Base58 => keypair.to_base58_string(),
+            SecretFormat::JsonArray => format!("{:?}", keypair.to_bytes()).replace(' ', ""),
+            SecretFormat::Bytes => keypair
+                .to_bytes()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct KeystoreArgs {
+    /// The path to the keypair file
+    pub keypair: PathBuf,
+    /// Where to write the result, defaults to replacing the keypair file
+    #[arg(short = 'o', long)]
+    pub output_file: Option<PathBuf>,
+    /// Overwrite the output file if it already exists
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct KeystoreConverted {
+    pub address: String,
+    pub output_file: String,
+    pub encrypted: bool,
+}
+
+impl fmt::Display for KeystoreConverted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = if self.encrypted {
+            "Encrypted"
+        } else {
+            "Decrypted"
+        };
+        write!(
+            f,
+            "{} keypair for {} saved to {}",
+            action,
+            self.address.blue().bold(),
+            self.output_file.green().bold()
+        )
+    }
+}
+
+impl CommandOutput for KeystoreConverted {}
+
+impl KeystoreArgs {
+    pub fn encrypt_handler(&self, settings: &Settings) -> Result<()> {
+        let contents = read_file(&self.keypair)?;
+        if is_keystore(&contents) {
+            anyhow::bail!("`{}` is already encrypted", self.keypair.display());
+        }
+        let keypair = parse_plain_keypair(&contents, &self.keypair)?;
+        let output_file = self.output_file()?;
+        write_encrypted_keypair(&keypair, output_file)?;
+        self.print(&keypair, output_file, true, settings)
+    }
+
+    pub fn decrypt_handler(&self, settings: &Settings) -> Result<()> {
+        if !is_keystore(&read_file(&self.keypair)?) {
+            anyhow::bail!("`{}` is not encrypted", self.keypair.display());
+        }
+        let output_file = self.output_file()?;
+        let keypair = read_keypair(&self.keypair)?;
+        write_plain_keypair(&keypair, output_file)?;
+        self.print(&keypair, output_file, false, settings)
+    }
+
+    /// The file to write, refusing to replace another file than the keypair
+    /// itself without `--force`
+    fn output_file(&self) -> Result<&Path> {
+        match &self.output_file {
+            Some(output_file) if *output_file != self.keypair => {
+                check_overwrite(output_file, self.force)?;
+                Ok(output_file)
+            }
+            _ => Ok(&self.keypair),
+        }
+    }
+
+    fn print(
+        &self,
+        keypair: &Keypair,
+        output_file: &Path,
+        encrypted: bool,
+        settings: &Settings,
+    ) -> Result<()> {
+        let output = KeystoreConverted {
+            address: keypair.pubkey().to_string(),
+            output_file: output_file.display().to_string(),
+            encrypted,
+        };
+        print_output(&output, settings.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_and_open_round_trip() {
+        let keypair = Keypair::new();
+        let keystore = Keystore::seal(&keypair, "correct horse").unwrap();
+        assert_eq!(keystore.pubkey, keypair.pubkey().to_string());
+
+        let json = serde_json::to_string_pretty(&keystore).unwrap();
+        assert!(is_keystore(&json));
+        let keystore: Keystore = serde_json::from_str(&json).unwrap();
+        let opened = keystore.open("correct horse").unwrap();
+        assert_eq!(opened.to_bytes(), keypair.to_bytes());
+
+        assert!(keystore.open("wrong horse").is_err());
+    }
+
+    #[test]
+    fn open_rejects_a_swapped_address() {
+        let mut keystore = Keystore::seal(&Keypair::new(), "passphrase").unwrap();
+        // The address is authenticated, so pointing it elsewhere fails to decrypt
+        keystore.pubkey = Keypair::new().pubkey().to_string();
+        let err = keystore.open("passphrase").unwrap_err();
+        assert_eq!(err.to_string(), "Wrong passphrase or corrupted keystore");
+    }
+
+    #[test]
+    fn open_rejects_unsupported_keystores() {
+        let mut keystore = Keystore::seal(&Keypair::new(), "passphrase").unwrap();
+        keystore.version = 2;
+        assert!(keystore.open("passphrase").is_err());
+        keystore.version = KEYSTORE_VERSION;
+        keystore.cipher.nonce = BASE64.encode([0u8; 12]);
+        assert!(keystore.open("passphrase").is_err());
+        keystore.kdf.name = "scrypt".to_string();
+        assert!(keystore.open("passphrase").is_err());
+    }
+
+    #[test]
+    fn open_refuses_costly_key_derivation() {
+        let mut keystore = Keystore::seal(&Keypair::new(), "passphrase").unwrap();
+        keystore.kdf.memory_kib = u32::MAX;
+        let err = keystore.open("passphrase").unwrap_err();
+        assert!(err.to_string().contains("exceed the supported maximum"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_files_replace_stale_temporary_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("id.json");
+        let tmp_path = dir.path().join(".id.json.tmp");
+        std::fs::write(&tmp_path, "stale").unwrap();
+        std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_private_file(&path, b"secret").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "secret");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!tmp_path.exists());
+    }
+
+    #[test]
+    fn plain_keypairs_are_not_keystores() {
+        let keypair = Keypair::new();
+        let plain = serde_json::to_string(&keypair.to_bytes().to_vec()).unwrap();
+        assert!(!is_keystore(&plain));
+        let parsed = parse_plain_keypair(&plain, Path::new("id.json")).unwrap();
+        assert_eq!(parsed.pubkey(), keypair.pubkey());
+    }
+
+    #[test]
+    fn exports_secret_formats() {
+        let keypair = Keypair::new();
+        let bytes = keypair.to_bytes();
+        assert_eq!(
+            SecretFormat::JsonArray.export(&keypair),
+            serde_json::to_string(&bytes.to_vec()).unwrap()
+        );
+        assert_eq!(SecretFormat::Bytes.export(&keypair).len(), 128);
+        let base58 = SecretFormat::Base58.export(&keypair);
+        assert_eq!(Keypair::from_base58_string(&base58), keypair);
+    }
+}
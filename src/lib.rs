@@ -1,16 +1,58 @@
 use anyhow::{Ok, Result};
 use args::{Cli, Commands};
 use clap::Parser;
+use config::{LoadedProfile, Settings};
+mod amount;
 mod args;
+mod batch;
+mod cleanup;
+mod config;
+mod grind;
+mod history;
+mod keystore;
+mod memo;
+mod mnemonic;
+mod output;
+mod policy;
+mod priority_fee;
+mod recipient;
+mod sender;
+mod signer;
+mod sweep;
+mod token;
+mod transaction;
+mod wsol;
 
 pub async fn run() -> Result<()> {
     let cli = Cli::parse();
+    if let Commands::Config(config_args) = &cli.command {
+        // Nothing in the profile is validated, so `config set` can fix a bad value
+        let loaded = LoadedProfile::load(&cli)?;
+        let format = loaded.output_or_default(&cli);
+        output::configure_colors(format, cli.no_color);
+        return config_args.config_handler(&loaded, format);
+    }
+    let settings = Settings::load(&cli)?;
+    output::configure_colors(settings.output, cli.no_color);
 
     match cli.command {
-        Commands::Generate(generate_args) => generate_args.generate_keypair()?,
-        Commands::Balance(wallet_args) => wallet_args.get_balance_handler().await?,
-        Commands::Airdrop(airdrop_args) => airdrop_args.request_airdrop_handler().await?,
-        Commands::Transfer(transfer_args) => transfer_args.transfer_handler().await?,
+        Commands::Generate(generate_args) => generate_args.generate_keypair(&settings)?,
+        Commands::Grind(grind_args) => grind_args.grind_handler(&settings)?,
+        Commands::Recover(recover_args) => recover_args.recover_keypair(&settings)?,
+        Commands::Encrypt(keystore_args) => keystore_args.encrypt_handler(&settings)?,
+        Commands::Decrypt(keystore_args) => keystore_args.decrypt_handler(&settings)?,
+        Commands::Balance(wallet_args) => wallet_args.get_balance_handler(&settings).await?,
+        Commands::History(history_args) => history_args.history_handler(&settings).await?,
+        Commands::Tx(tx_args) => tx_args.tx_handler(&settings).await?,
+        Commands::Airdrop(airdrop_args) => airdrop_args.request_airdrop_handler(&settings).await?,
+        Commands::Transfer(transfer_args) => transfer_args.transfer_handler(&settings).await?,
+        Commands::TransferBatch(batch_args) => batch_args.transfer_batch_handler(&settings).await?,
+        Commands::Sweep(sweep_args) => sweep_args.sweep_handler(&settings).await?,
+        Commands::Token(token_args) => token_args.token_handler(&settings).await?,
+        Commands::Wrap(wrap_args) => wrap_args.wrap_handler(&settings).await?,
+        Commands::Unwrap(unwrap_args) => unwrap_args.unwrap_handler(&settings).await?,
+        Commands::Cleanup(cleanup_args) => cleanup_args.cleanup_handler(&settings).await?,
+        Commands::Config(_) => unreachable!("`config` runs before the settings are loaded"),
     }
     Ok(())
 }
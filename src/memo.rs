@@ -0,0 +1,48 @@
+use anyhow::Result;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+
+/// Longest memo in bytes, which leaves room in the 1232 byte transaction for
+/// the transfer, the token account creation and the compute budget
+/// instructions sent alongside it
+pub const MAX_MEMO_BYTES: usize = 566;
+
+/// Parses the `--memo` flag
+pub fn parse_memo(memo: &str) -> Result<String> {
+    if memo.trim().is_empty() {
+        anyhow::bail!("The memo can not be empty");
+    }
+    if memo.len() > MAX_MEMO_BYTES {
+        anyhow::bail!(
+            "The memo is {} bytes, at most {} bytes fit in a transaction",
+            memo.len(),
+            MAX_MEMO_BYTES
+        );
+    }
+    Ok(memo.to_string())
+}
+
+/// An SPL Memo instruction carrying `memo`, signed by `signer` so the memo
+/// is attributed to the sender
+pub fn memo_instruction(memo: &str, signer: &Pubkey) -> Instruction {
+    spl_memo::build_memo(memo.as_bytes(), &[signer])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_memo_limits_the_length_in_bytes() {
+        assert_eq!(parse_memo("invoice 42").unwrap(), "invoice 42");
+        assert!(parse_memo("  ").is_err());
+        assert!(parse_memo(&"a".repeat(MAX_MEMO_BYTES)).is_ok());
+        let err = parse_memo(&"a".repeat(MAX_MEMO_BYTES + 1)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The memo is 567 bytes, at most 566 bytes fit in a transaction"
+        );
+        // Multi-byte characters count by their UTF-8 length
+        assert!(parse_memo(&"é".repeat(MAX_MEMO_BYTES / 2 + 1)).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use bip39::{Language, Mnemonic};
+use solana_sdk::derivation_path::DerivationPath;
+use solana_sdk::signature::{keypair_from_seed, keypair_from_seed_and_derivation_path, Keypair};
+use std::io::IsTerminal;
+
+/// The path used by Phantom, Solflare and most other wallets
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/501'/0'/0'";
+
+/// A new random English seed phrase of `words` words
+pub fn generate_mnemonic(words: usize) -> Result<Mnemonic> {
+    Mnemonic::generate_in(Language::English, words)
+        .map_err(|e| anyhow::anyhow!("Failed to generate a seed phrase: {}", e))
+}
+
+/// Parses either a full path like `m/44'/501'/1'/0'` or the Solana CLI
+/// `<account>/<change>` shorthand like `1/0`
+pub fn parse_derivation_path(path: &str) -> Result<DerivationPath> {
+    let derivation_path = if path.starts_with('m') {
+        DerivationPath::from_absolute_path_str(path)
+    } else {
+        DerivationPath::from_key_str(path)
+    };
+    derivation_path.with_context(|| format!("Invalid derivation path `{}`", path))
+}
+
+/// Derives the keypair for `phrase`, either from `derivation_path` or, when
+/// it is `None`, the way `solana-keygen` does without a path
+pub fn keypair_from_mnemonic(
+    phrase: &str,
+    passphrase: &str,
+    derivation_path: Option<DerivationPath>,
+) -> Result<Keypair> {
+    let mnemonic = Mnemonic::parse_in_normalized(Language::English, phrase)
+        .map_err(|e| anyhow::anyhow!("Invalid seed phrase: {}", e))?;
+    let seed = mnemonic.to_seed(passphrase);
+    let keypair = match derivation_path {
+        Some(derivation_path) => {
+            keypair_from_seed_and_derivation_path(&seed, Some(derivation_path))
+        }
+        None => keypair_from_seed(&seed[..32]),
+    };
+    keypair.map_err(|e| anyhow::anyhow!("Failed to derive keypair from seed phrase: {}", e))
+}
+
+/// Reads a secret without echoing it on a terminal, or as a plain line when
+/// stdin is piped
+pub fn read_secret(prompt: &str) -> Result<String> {
+    let secret = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password(prompt)?
+    } else {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        line
+    };
+    Ok(secret.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Prompts for a BIP39 passphrase twice, so typos don't lock funds away
+pub fn prompt_new_passphrase() -> Result<String> {
+    let passphrase = read_secret("BIP39 passphrase: ")?;
+    if std::io::stdin().is_terminal() {
+        let confirmation = read_secret("Confirm BIP39 passphrase: ")?;
+        if passphrase != confirmation {
+            anyhow::bail!("Passphrases do not match");
+        }
+    }
+    Ok(passphrase)
+}
@@ -0,0 +1,503 @@
+use crate::amount::format_sol;
+use crate::sender::Stage;
+use anyhow::Result;
+use clap::ValueEnum;
+use colored::*;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Colored text for humans
+    #[default]
+    Human,
+    Json,
+    Yaml,
+    Csv,
+}
+
+/// A command result that can be rendered in every `OutputFormat`, the
+/// `Display` impl is the human readable form
+pub trait CommandOutput: Serialize + fmt::Display {
+    /// Writes the CSV records for this result, by default a single row made
+    /// of the struct's fields
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        writer.serialize(self)?;
+        Ok(())
+    }
+}
+
+/// A list of results of the same kind, rendered as a JSON/YAML array, one
+/// CSV row per item or one human readable block per item
+#[derive(Serialize, Debug)]
+#[serde(transparent)]
+pub struct OutputList<T>(pub Vec<T>);
+
+impl<T: CommandOutput> fmt::Display for OutputList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, item) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", item)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: CommandOutput> CommandOutput for OutputList<T> {
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        for item in &self.0 {
+            item.write_csv(writer)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn print_output<T: CommandOutput>(output: &T, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Human => println!("{}", output),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(output)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(output)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            output.write_csv(&mut writer)?;
+            print!("{}", String::from_utf8(writer.into_inner()?)?);
+        }
+    }
+    Ok(())
+}
+
+/// Prints a single result on its own and several results as a list
+pub fn print_outputs<T: CommandOutput>(mut outputs: Vec<T>, format: OutputFormat) -> Result<()> {
+    if outputs.len() == 1 {
+        return print_output(&outputs.remove(0), format);
+    }
+    print_output(&OutputList(outputs), format)
+}
+
+/// Turns colors off for `--no-color`, the `NO_COLOR` environment variable and
+/// any machine-readable output
+pub fn configure_colors(format: OutputFormat, no_color: bool) {
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    if no_color || no_color_env || format != OutputFormat::Human {
+        colored::control::set_override(false);
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct KeypairGenerated {
+    pub address: String,
+    /// `None` with `--no-outfile`
+    pub output_file: Option<String>,
+    /// Only exported with `--show-secret`
+    pub secret_key: Option<String>,
+    pub mnemonic: Option<String>,
+    pub derivation_path: Option<String>,
+}
+
+impl fmt::Display for KeypairGenerated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.output_file {
+            Some(output_file) => writeln!(f, "Keypair saved to {}", output_file.green().bold())?,
+            None => writeln!(f, "{}", "Keypair was not saved to a file".yellow().bold())?,
+        }
+        writeln!(f, "Wallet address: {}", self.address.blue().bold())?;
+        if let Some(secret_key) = &self.secret_key {
+            writeln!(f, "Secret key:\n{}", secret_key)?;
+        }
+        if let (Some(mnemonic), Some(derivation_path)) = (&self.mnemonic, &self.derivation_path) {
+            writeln!(f, "Seed phrase: {}", mnemonic.bold())?;
+            writeln!(f, "Derivation path: {}", derivation_path)?;
+            writeln!(
+                f,
+                "{}",
+                "Write the seed phrase down and keep it offline, it recovers this wallet"
+                    .yellow()
+                    .bold()
+            )?;
+        }
+        write!(
+            f,
+            "{}",
+            "DO NOT SHARE THIS KEYPAIR OR THE KEYPAIR FILE WITH ANYONE"
+                .red()
+                .bold()
+        )
+    }
+}
+
+impl CommandOutput for KeypairGenerated {
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        writer.write_record([
+            "address",
+            "output_file",
+            "secret_key",
+            "mnemonic",
+            "derivation_path",
+        ])?;
+        writer.write_record([
+            self.address.clone(),
+            self.output_file.clone().unwrap_or_default(),
+            self.secret_key.clone().unwrap_or_default(),
+            self.mnemonic.clone().unwrap_or_default(),
+            self.derivation_path.clone().unwrap_or_default(),
+        ])?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct KeypairRecovered {
+    pub address: String,
+    pub output_file: String,
+    /// `None` for keypairs derived the legacy `solana-keygen` way
+    pub derivation_path: Option<String>,
+}
+
+impl fmt::Display for KeypairRecovered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Keypair saved to {}", self.output_file.green().bold())?;
+        writeln!(f, "Wallet address: {}", self.address.blue().bold())?;
+        write!(
+            f,
+            "Derivation path: {}",
+            self.derivation_path.as_deref().unwrap_or("none (legacy)")
+        )
+    }
+}
+
+impl CommandOutput for KeypairRecovered {}
+
+#[derive(Serialize, Debug)]
+pub struct WalletBalance {
+    pub address: String,
+    pub lamports: u64,
+    pub sol: String,
+    /// Only listed with `--tokens`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<Vec<TokenBalance>>,
+}
+
+impl WalletBalance {
+    pub fn new(address: String, lamports: u64) -> Self {
+        Self {
+            address,
+            lamports,
+            sol: format_sol(lamports),
+            tokens: None,
+        }
+    }
+}
+
+impl fmt::Display for WalletBalance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Your SOL balance is: {}", self.sol.green().bold())?;
+        match &self.tokens {
+            Some(tokens) if tokens.is_empty() => write!(f, "\nNo token accounts found"),
+            Some(tokens) => {
+                write!(f, "\nToken accounts:")?;
+                for token in tokens {
+                    write!(f, "\n  {}", token)?;
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// One CSV row per token account, repeating the wallet's SOL balance
+#[derive(Serialize)]
+struct TokenBalanceRow<'a> {
+    address: &'a str,
+    lamports: u64,
+    sol: &'a str,
+    account: &'a str,
+    mint: &'a str,
+    program: &'a str,
+    amount: &'a str,
+    decimals: u8,
+    ui_amount: &'a str,
+    frozen: bool,
+    delegate: Option<&'a str>,
+    delegated_amount: Option<&'a str>,
+}
+
+impl CommandOutput for WalletBalance {
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        let Some(tokens) = &self.tokens else {
+            writer.serialize(self)?;
+            return Ok(());
+        };
+        for token in tokens {
+            writer.serialize(TokenBalanceRow {
+                address: &self.address,
+                lamports: self.lamports,
+                sol: &self.sol,
+                account: &token.account,
+                mint: &token.mint,
+                program: &token.program,
+                amount: &token.amount,
+                decimals: token.decimals,
+                ui_amount: &token.ui_amount,
+                frozen: token.frozen,
+                delegate: token.delegate.as_deref(),
+                delegated_amount: token.delegated_amount.as_deref(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TokenBalance {
+    /// The token account holding the balance
+    pub account: String,
+    pub mint: String,
+    /// token or token-2022
+    pub program: String,
+    /// Raw amount in the mint's smallest unit
+    pub amount: String,
+    pub decimals: u8,
+    pub ui_amount: String,
+    pub frozen: bool,
+    pub delegate: Option<String>,
+    /// UI amount the delegate may still transfer
+    pub delegated_amount: Option<String>,
+}
+
+impl fmt::Display for TokenBalance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of mint {} ({}) in {}",
+            self.ui_amount.green().bold(),
+            self.mint.blue(),
+            self.program,
+            self.account
+        )?;
+        if self.frozen {
+            write!(f, " {}", "[frozen]".red())?;
+        }
+        if let (Some(delegate), Some(delegated_amount)) = (&self.delegate, &self.delegated_amount) {
+            write!(
+                f,
+                " {}",
+                format!("[{} delegated to {}]", delegated_amount, delegate).yellow()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct AirdropRequested {
+    pub address: String,
+    pub lamports: u64,
+    pub sol: String,
+    pub signature: String,
+    /// Only known with `--wait`
+    pub status: Option<Stage>,
+    pub slot: Option<u64>,
+    /// Balance of the wallet once the airdrop landed
+    pub balance: Option<u64>,
+}
+
+impl fmt::Display for AirdropRequested {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Airdrop requested successfully, signature: {}",
+            self.signature.yellow().bold()
+        )?;
+        if let (Some(status), Some(slot)) = (self.status, self.slot) {
+            write!(
+                f,
+                "\nAirdrop of {} SOL {} in slot {}",
+                self.sol, status, slot
+            )?;
+        }
+        if let Some(balance) = self.balance {
+            write!(
+                f,
+                "\nYour SOL balance is: {}",
+                format_sol(balance).green().bold()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl CommandOutput for AirdropRequested {}
+
+pub fn format_fee(fee: u64, priority_fee: u64) -> String {
+    if priority_fee == 0 {
+        return format!("{} SOL", format_sol(fee));
+    }
+    format!(
+        "{} SOL (including a {} SOL priority fee)",
+        format_sol(fee),
+        format_sol(priority_fee)
+    )
+}
+
+#[derive(Serialize, Debug)]
+pub struct TransferCompleted {
+    pub from: String,
+    pub to: String,
+    pub lamports: u64,
+    pub sol: String,
+    /// Total fee in lamports, including `priority_fee`
+    pub fee: u64,
+    pub priority_fee: u64,
+    pub memo: Option<String>,
+    pub signature: String,
+    pub slot: u64,
+    pub status: Stage,
+    /// How many times the transaction was re-signed after its blockhash expired
+    pub resigned: usize,
+}
+
+impl fmt::Display for TransferCompleted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Transferred {} SOL", self.sol.green().bold())?;
+        if let Some(memo) = &self.memo {
+            writeln!(f, "Memo: {}", memo)?;
+        }
+        writeln!(f, "Fee: {}", format_fee(self.fee, self.priority_fee))?;
+        writeln!(f, "Status: {} in slot {}", self.status, self.slot)?;
+        if self.resigned > 0 {
+            writeln!(
+                f,
+                "Re-signed {} time(s) after the blockhash expired",
+                self.resigned
+            )?;
+        }
+        write!(
+            f,
+            "Transfer successful, signature: {}",
+            self.signature.yellow().bold()
+        )
+    }
+}
+
+impl CommandOutput for TransferCompleted {}
+
+#[derive(Serialize, Debug)]
+pub struct TransferSimulation {
+    pub from: String,
+    pub to: String,
+    pub lamports: u64,
+    pub sol: String,
+    /// Total fee in lamports, including `priority_fee`
+    pub fee: u64,
+    pub priority_fee: u64,
+    pub units_consumed: Option<u64>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub from_balance_before: u64,
+    pub from_balance_after: Option<u64>,
+    pub to_balance_before: u64,
+    pub to_balance_after: Option<u64>,
+    pub memo: Option<String>,
+    pub logs: Vec<String>,
+}
+
+fn format_balance_change(before: u64, after: Option<u64>) -> String {
+    match after {
+        Some(after) => format!("{} -> {} SOL", format_sol(before), format_sol(after)),
+        None => format!("{} SOL -> unknown", format_sol(before)),
+    }
+}
+
+impl fmt::Display for TransferSimulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", "Dry run, nothing was broadcast".yellow().bold())?;
+        writeln!(
+            f,
+            "Transfer {} SOL from {} to {}",
+            self.sol.green().bold(),
+            self.from,
+            self.to
+        )?;
+        if let Some(memo) = &self.memo {
+            writeln!(f, "Memo: {}", memo)?;
+        }
+        writeln!(f, "Fee: {}", format_fee(self.fee, self.priority_fee))?;
+        if let Some(units_consumed) = self.units_consumed {
+            writeln!(f, "Compute units consumed: {}", units_consumed)?;
+        }
+        writeln!(
+            f,
+            "Sender balance: {}",
+            format_balance_change(self.from_balance_before, self.from_balance_after)
+        )?;
+        writeln!(
+            f,
+            "Recipient balance: {}",
+            format_balance_change(self.to_balance_before, self.to_balance_after)
+        )?;
+        match &self.error {
+            Some(error) => write!(f, "Simulation failed: {}", error.red().bold())?,
+            None => write!(
+                f,
+                "Simulation succeeded: {}",
+                "the transfer would land".green()
+            )?,
+        }
+        if !self.logs.is_empty() {
+            write!(f, "\nLogs:")?;
+            for log in &self.logs {
+                write!(f, "\n  {}", log)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CommandOutput for TransferSimulation {
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        writer.write_record([
+            "from",
+            "to",
+            "lamports",
+            "sol",
+            "fee",
+            "priority_fee",
+            "units_consumed",
+            "success",
+            "error",
+            "from_balance_before",
+            "from_balance_after",
+            "to_balance_before",
+            "to_balance_after",
+            "memo",
+            "logs",
+        ])?;
+        writer.write_record([
+            self.from.clone(),
+            self.to.clone(),
+            self.lamports.to_string(),
+            self.sol.clone(),
+            self.fee.to_string(),
+            self.priority_fee.to_string(),
+            self.units_consumed
+                .map(|u| u.to_string())
+                .unwrap_or_default(),
+            self.success.to_string(),
+            self.error.clone().unwrap_or_default(),
+            self.from_balance_before.to_string(),
+            self.from_balance_after
+                .map(|b| b.to_string())
+                .unwrap_or_default(),
+            self.to_balance_before.to_string(),
+            self.to_balance_after
+                .map(|b| b.to_string())
+                .unwrap_or_default(),
+            self.memo.clone().unwrap_or_default(),
+            self.logs.join("\n"),
+        ])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,409 @@
+use crate::amount::{format_sol, Amount};
+use crate::args::Network;
+use crate::config::Settings;
+use crate::token::parse_pubkey;
+use anyhow::{Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Genesis hash of mainnet-beta, how an RPC URL is recognized as mainnet
+const MAINNET_GENESIS_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+
+/// The window `daily_limit` applies to
+const DAILY_LIMIT_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// Limits on the SOL sent on mainnet, set in a config profile
+#[derive(Clone, Debug, Default)]
+pub struct SpendingPolicy {
+    /// Most lamports sent to a single recipient at once
+    pub max_transfer: Option<u64>,
+    /// Most lamports sent from one wallet over the last 24 hours
+    pub daily_limit: Option<u64>,
+    /// The only addresses SOL and tokens may be sent to
+    pub allowlist: Option<Vec<Pubkey>>,
+}
+
+impl SpendingPolicy {
+    pub fn parse_limit(value: &str) -> Result<u64> {
+        Amount::from_str(value)?.lamports("a spending limit")
+    }
+
+    /// Parses a comma separated list of addresses
+    pub fn parse_allowlist(value: &str) -> Result<Vec<Pubkey>> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .map(|address| parse_pubkey(address, "allowlist"))
+            .collect()
+    }
+}
+
+/// A SOL transfer recorded in the ledger
+#[derive(Serialize, Deserialize, Debug)]
+struct LedgerEntry {
+    /// Seconds since the unix epoch when the transfer was signed
+    timestamp: u64,
+    from: String,
+    to: String,
+    lamports: u64,
+    /// The first signature the transfer was sent with
+    signature: String,
+    /// The signatures it was re-signed with after a blockhash expired
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    resigned: Vec<String>,
+}
+
+/// Every SOL transfer signed on mainnet, kept next to the config file so the
+/// daily limit holds across runs
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct Ledger {
+    entries: Vec<LedgerEntry>,
+}
+
+impl Ledger {
+    fn load(path: &Path) -> Result<Ledger> {
+        if !path.exists() {
+            return Ok(Ledger::default());
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read ledger {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse ledger {}", path.display()))
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("Failed to create ledger directory {}", parent.display())
+            })?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(&temp_path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write ledger {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, path)
+            .with_context(|| format!("Failed to write ledger {}", path.display()))
+    }
+
+    /// Lamports sent from `from` since `since`
+    fn spent_since(&self, from: &Pubkey, since: u64) -> u64 {
+        let from = from.to_string();
+        self.entries
+            .iter()
+            .filter(|entry| entry.from == from && entry.timestamp >= since)
+            .map(|entry| entry.lamports)
+            .sum()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Whether the cluster behind `rpc_client` is mainnet, custom RPC URLs are
+/// recognized by their genesis hash
+async fn is_mainnet(rpc_client: &RpcClient, network: &Network) -> Result<bool> {
+    match network {
+        Network::Mainnet => Ok(true),
+        Network::Custom(_) => {
+            let genesis_hash = rpc_client
+                .get_genesis_hash()
+                .await
+                .context("Failed to look up the genesis hash of the cluster")?;
+            Ok(genesis_hash.to_string() == MAINNET_GENESIS_HASH)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// The confirmation prompt and spending policy of commands sending SOL, both
+/// only apply on mainnet
+pub struct MainnetGuard {
+    mainnet: bool,
+    url: String,
+    policy: SpendingPolicy,
+    ledger_file: PathBuf,
+}
+
+impl MainnetGuard {
+    pub async fn new(rpc_client: &RpcClient, settings: &Settings) -> Result<MainnetGuard> {
+        Ok(MainnetGuard {
+            mainnet: is_mainnet(rpc_client, &settings.network).await?,
+            url: settings.network.url().to_string(),
+            policy: settings.policy.clone(),
+            ledger_file: settings.config_file.with_file_name("ledger.json"),
+        })
+    }
+
+    /// Refuses `transfers` of lamports from `from` that break the spending
+    /// policy
+    pub fn check(&self, from: &Pubkey, transfers: &[(Pubkey, u64)]) -> Result<()> {
+        if !self.mainnet {
+            return Ok(());
+        }
+        for (to, _) in transfers {
+            self.check_allowlist(to)?;
+        }
+        if let Some(max_transfer) = self.policy.max_transfer {
+            if let Some((to, lamports)) = transfers.iter().find(|(_, l)| *l > max_transfer) {
+                anyhow::bail!(
+                    "Sending {} SOL to {} exceeds the {} SOL per transfer limit of this profile",
+                    format_sol(*lamports),
+                    to,
+                    format_sol(max_transfer)
+                );
+            }
+        }
+        if let Some(daily_limit) = self.policy.daily_limit {
+            let ledger = Ledger::load(&self.ledger_file)?;
+            let spent = ledger.spent_since(from, now().saturating_sub(DAILY_LIMIT_WINDOW_SECS));
+            let sending = transfers.iter().map(|(_, l)| *l).sum::<u64>();
+            if spent.saturating_add(sending) > daily_limit {
+                anyhow::bail!(
+                    "Sending {} SOL exceeds the {} SOL daily limit of this profile, {} already sent {} SOL in the last 24 hours",
+                    format_sol(sending),
+                    format_sol(daily_limit),
+                    from,
+                    format_sol(spent)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuses sending to `to` when the profile has an allowlist without it,
+    /// the only part of the policy that applies to tokens
+    pub fn check_allowlist(&self, to: &Pubkey) -> Result<()> {
+        if !self.mainnet {
+            return Ok(());
+        }
+        match &self.policy.allowlist {
+            Some(allowlist) if !allowlist.contains(to) => {
+                anyhow::bail!("{} is not in the allowlist of this profile", to)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Shows `summary` and asks to go ahead, unless `yes` is set
+    pub fn confirm(&self, summary: &[String], yes: bool) -> Result<()> {
+        if !self.mainnet || yes {
+            return Ok(());
+        }
+        if !std::io::stdin().is_terminal() {
+            anyhow::bail!(
+                "Refusing to send on mainnet without confirmation, pass `--yes` to confirm"
+            );
+        }
+        let mut stderr = std::io::stderr();
+        writeln!(
+            stderr,
+            "About to send on {} ({}):",
+            "mainnet".red().bold(),
+            self.url
+        )?;
+        for line in summary {
+            writeln!(stderr, "  {}", line)?;
+        }
+        write!(stderr, "Send? [y/N] ")?;
+        stderr.flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            anyhow::bail!("Cancelled, nothing was sent");
+        }
+        Ok(())
+    }
+
+    /// Records signed `transfers` in the ledger before they are sent, so a run
+    /// that is interrupted still counts them towards the daily limit. The
+    /// first call sets `first` to the signature, later calls add the
+    /// signatures of a re-signed transaction to the same entries. Sends that
+    /// definitely did not land are taken out again with `forget`
+    pub fn record(
+        &self,
+        from: &Pubkey,
+        transfers: &[(Pubkey, u64)],
+        first: &mut Option<Signature>,
+        signature: &Signature,
+    ) -> Result<()> {
+        if !self.mainnet {
+            return Ok(());
+        }
+        let mut ledger = Ledger::load(&self.ledger_file)?;
+        match first {
+            Some(first) => {
+                let first = first.to_string();
+                for entry in ledger.entries.iter_mut().filter(|e| e.signature == first) {
+                    entry.resigned.push(signature.to_string());
+                }
+            }
+            None => {
+                let timestamp = now();
+                for (to, lamports) in transfers {
+                    ledger.entries.push(LedgerEntry {
+                        timestamp,
+                        from: from.to_string(),
+                        to: to.to_string(),
+                        lamports: *lamports,
+                        signature: signature.to_string(),
+                        resigned: Vec::new(),
+                    });
+                }
+                *first = Some(*signature);
+            }
+        }
+        ledger.save(&self.ledger_file)
+    }
+
+    /// Removes the transfers recorded under the first `signature` of a send
+    /// that definitely did not land, so it does not count towards the daily
+    /// limit. Sends whose outcome is unknown stay recorded
+    pub fn forget(&self, signature: &Signature) -> Result<()> {
+        if !self.mainnet {
+            return Ok(());
+        }
+        let mut ledger = Ledger::load(&self.ledger_file)?;
+        let signature = signature.to_string();
+        ledger.entries.retain(|entry| entry.signature != signature);
+        ledger.save(&self.ledger_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn guard(dir: &TempDir, mainnet: bool, policy: SpendingPolicy) -> MainnetGuard {
+        MainnetGuard {
+            mainnet,
+            url: "https://api.mainnet-beta.solana.com".to_string(),
+            policy,
+            ledger_file: dir.path().join("ledger.json"),
+        }
+    }
+
+    fn daily_limit(lamports: u64) -> SpendingPolicy {
+        SpendingPolicy {
+            daily_limit: Some(lamports),
+            ..SpendingPolicy::default()
+        }
+    }
+
+    #[test]
+    fn records_every_signature_of_a_send() {
+        let dir = TempDir::new().unwrap();
+        let guard = guard(&dir, true, SpendingPolicy::default());
+        let from = Pubkey::new_unique();
+        let transfers = [(Pubkey::new_unique(), SOL), (Pubkey::new_unique(), 2 * SOL)];
+        let (signature, resigned) = (Signature::new_unique(), Signature::new_unique());
+        let mut first = None;
+        guard
+            .record(&from, &transfers, &mut first, &signature)
+            .unwrap();
+        guard
+            .record(&from, &transfers, &mut first, &resigned)
+            .unwrap();
+        assert_eq!(first, Some(signature));
+
+        let ledger = Ledger::load(&guard.ledger_file).unwrap();
+        assert_eq!(ledger.entries.len(), 2);
+        for entry in &ledger.entries {
+            assert_eq!(entry.signature, signature.to_string());
+            assert_eq!(entry.resigned, [resigned.to_string()]);
+        }
+        assert_eq!(ledger.spent_since(&from, 0), 3 * SOL);
+
+        guard.forget(&signature).unwrap();
+        assert!(Ledger::load(&guard.ledger_file).unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn daily_limit_counts_recorded_transfers() {
+        let dir = TempDir::new().unwrap();
+        let guard = guard(&dir, true, daily_limit(5 * SOL));
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let signature = Signature::new_unique();
+        guard
+            .record(&from, &[(to, 3 * SOL)], &mut None, &signature)
+            .unwrap();
+
+        assert!(guard.check(&from, &[(to, 2 * SOL)]).is_ok());
+        let err = guard.check(&from, &[(to, 2 * SOL + 1)]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("already sent 3 SOL in the last 24 hours"));
+        // The limit is per wallet
+        assert!(guard.check(&Pubkey::new_unique(), &[(to, 5 * SOL)]).is_ok());
+
+        guard.forget(&signature).unwrap();
+        assert!(guard.check(&from, &[(to, 5 * SOL)]).is_ok());
+    }
+
+    #[test]
+    fn daily_limit_ignores_older_transfers() {
+        let dir = TempDir::new().unwrap();
+        let guard = guard(&dir, true, daily_limit(5 * SOL));
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let ledger = Ledger {
+            entries: vec![LedgerEntry {
+                timestamp: now() - DAILY_LIMIT_WINDOW_SECS - 60,
+                from: from.to_string(),
+                to: to.to_string(),
+                lamports: 5 * SOL,
+                signature: Signature::new_unique().to_string(),
+                resigned: Vec::new(),
+            }],
+        };
+        ledger.save(&guard.ledger_file).unwrap();
+        assert!(guard.check(&from, &[(to, 5 * SOL)]).is_ok());
+    }
+
+    #[test]
+    fn refuses_large_transfers_and_unknown_recipients() {
+        let dir = TempDir::new().unwrap();
+        let allowed = Pubkey::new_unique();
+        let policy = SpendingPolicy {
+            max_transfer: Some(SOL),
+            allowlist: Some(vec![allowed]),
+            ..SpendingPolicy::default()
+        };
+        let guard = guard(&dir, true, policy);
+        let from = Pubkey::new_unique();
+        assert!(guard.check(&from, &[(allowed, SOL)]).is_ok());
+        assert!(guard.check(&from, &[(allowed, SOL + 1)]).is_err());
+        let stranger = Pubkey::new_unique();
+        assert!(guard.check(&from, &[(stranger, 1)]).is_err());
+        assert!(guard.check_allowlist(&stranger).is_err());
+    }
+
+    #[test]
+    fn does_nothing_off_mainnet() {
+        let dir = TempDir::new().unwrap();
+        let guard = guard(&dir, false, daily_limit(1));
+        let from = Pubkey::new_unique();
+        let transfers = [(Pubkey::new_unique(), 5 * SOL)];
+        assert!(guard.check(&from, &transfers).is_ok());
+        let mut first = None;
+        guard
+            .record(&from, &transfers, &mut first, &Signature::new_unique())
+            .unwrap();
+        assert_eq!(first, None);
+        assert!(!guard.ledger_file.exists());
+        assert!(guard.confirm(&[], false).is_ok());
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::Transaction;
+
+/// The most compute units a transaction can request
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+// Compute budget flags for commands that send a transaction
+//
+// Like every flattened args struct this has a plain comment, clap would use a
+// doc comment as the about text of the command it is flattened into
+#[derive(Args, Clone, Debug, Default)]
+pub struct PriorityFeeArgs {
+    /// Priority fee in micro-lamports per compute unit
+    #[arg(long, conflicts_with = "auto_priority_fee")]
+    pub priority_fee: Option<u64>,
+    /// Compute unit limit for the transaction, estimated through a simulation
+    /// when a priority fee is set without one
+    #[arg(long)]
+    pub compute_unit_limit: Option<u32>,
+    /// Derive the priority fee from the fees recently paid to write to the
+    /// same accounts
+    #[arg(long)]
+    pub auto_priority_fee: bool,
+    /// Percentile of the recent prioritization fees used by `--auto-priority-fee`
+    #[arg(
+        long,
+        default_value_t = 75,
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        requires = "auto_priority_fee"
+    )]
+    pub priority_fee_percentile: u8,
+    /// Upper bound in micro-lamports per compute unit for `--auto-priority-fee`
+    #[arg(long, requires = "auto_priority_fee")]
+    pub max_priority_fee: Option<u64>,
+}
+
+/// The compute budget instructions prepended to a transaction
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ComputeBudget {
+    /// Micro-lamports per compute unit
+    pub unit_price: Option<u64>,
+    pub unit_limit: Option<u32>,
+}
+
+impl ComputeBudget {
+    pub fn instructions(&self) -> Vec<Instruction> {
+        let mut instructions = Vec::new();
+        if let Some(unit_limit) = self.unit_limit {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(unit_limit));
+        }
+        if let Some(unit_price) = self.unit_price {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(unit_price));
+        }
+        instructions
+    }
+
+    /// Lamports paid on top of the signature fees, the runtime charges the
+    /// price for every requested unit and rounds up
+    pub fn priority_fee(&self) -> u64 {
+        match (self.unit_price, self.unit_limit) {
+            (Some(unit_price), Some(unit_limit)) => {
+                let micro_lamports = unit_price as u128 * unit_limit as u128;
+                micro_lamports.div_ceil(MICRO_LAMPORTS_PER_LAMPORT) as u64
+            }
+            _ => 0,
+        }
+    }
+
+    /// `instructions` with the compute budget instructions in front
+    pub fn with_instructions(&self, instructions: &[Instruction]) -> Vec<Instruction> {
+        let mut all = self.instructions();
+        all.extend_from_slice(instructions);
+        all
+    }
+}
+
+impl PriorityFeeArgs {
+    /// Resolves the flags into the compute budget for a transaction made of
+    /// `instructions` and paid for by `payer`
+    pub async fn compute_budget(
+        &self,
+        rpc_client: &RpcClient,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+    ) -> Result<ComputeBudget> {
+        let unit_price = match self.priority_fee {
+            Some(priority_fee) => Some(priority_fee),
+            None if self.auto_priority_fee => Some(
+                self.recent_priority_fee(rpc_client, instructions, payer)
+                    .await?,
+            ),
+            None => None,
+        };
+        let unit_limit = match (self.compute_unit_limit, unit_price) {
+            (Some(unit_limit), _) => Some(unit_limit),
+            (None, Some(_)) => {
+                Some(estimate_compute_units(rpc_client, instructions, payer, unit_price).await?)
+            }
+            (None, None) => None,
+        };
+        Ok(ComputeBudget {
+            unit_price,
+            unit_limit,
+        })
+    }
+
+    async fn recent_priority_fee(
+        &self,
+        rpc_client: &RpcClient,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+    ) -> Result<u64> {
+        let mut writable_accounts = vec![*payer];
+        for account in instructions.iter().flat_map(|ix| &ix.accounts) {
+            if account.is_writable && !writable_accounts.contains(&account.pubkey) {
+                writable_accounts.push(account.pubkey);
+            }
+        }
+        let mut fees = rpc_client
+            .get_recent_prioritization_fees(&writable_accounts)
+            .await
+            .context("Failed to fetch recent prioritization fees")?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect::<Vec<_>>();
+        fees.sort_unstable();
+        let fee = percentile(&fees, self.priority_fee_percentile);
+        Ok(match self.max_priority_fee {
+            Some(max_priority_fee) => fee.min(max_priority_fee),
+            None => fee,
+        })
+    }
+}
+
+/// Nearest-rank percentile of sorted `values`, 0 when there are none
+fn percentile(values: &[u64], percentile: u8) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    let rank = (percentile as usize * values.len()).div_ceil(100);
+    values[rank.saturating_sub(1).min(values.len() - 1)]
+}
+
+/// Simulates the instructions with the maximum limit and returns the units
+/// consumed plus a 10% margin
+async fn estimate_compute_units(
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    unit_price: Option<u64>,
+) -> Result<u32> {
+    let budget = ComputeBudget {
+        unit_price,
+        unit_limit: Some(MAX_COMPUTE_UNIT_LIMIT),
+    };
+    let transaction =
+        Transaction::new_with_payer(&budget.with_instructions(instructions), Some(payer));
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(rpc_client.commitment()),
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let result = rpc_client
+        .simulate_transaction_with_config(&transaction, config)
+        .await
+        .context("Failed to simulate the transaction to estimate compute units")?
+        .value;
+    if let Some(err) = result.err {
+        anyhow::bail!(
+            "Simulating the transaction to estimate compute units failed: {}",
+            err
+        );
+    }
+    let units_consumed = result
+        .units_consumed
+        .context("The RPC node did not report the compute units consumed")?;
+    let with_margin = units_consumed + units_consumed / 10;
+    Ok(with_margin.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_of_no_fees_is_zero() {
+        assert_eq!(percentile(&[], 50), 0);
+        assert_eq!(percentile(&[], 100), 0);
+    }
+
+    #[test]
+    fn percentile_uses_the_nearest_rank() {
+        let fees = [10, 20, 30, 40, 50, 60, 70, 80, 90, 100];
+        assert_eq!(percentile(&fees, 0), 10);
+        assert_eq!(percentile(&fees, 1), 10);
+        assert_eq!(percentile(&fees, 50), 50);
+        assert_eq!(percentile(&fees, 51), 60);
+        assert_eq!(percentile(&fees, 75), 80);
+        assert_eq!(percentile(&fees, 90), 90);
+        assert_eq!(percentile(&fees, 100), 100);
+    }
+
+    #[test]
+    fn percentile_of_one_fee_is_that_fee() {
+        for p in [0, 25, 50, 100] {
+            assert_eq!(percentile(&[7], p), 7);
+        }
+    }
+
+    #[test]
+    fn percentile_rounds_up_between_ranks() {
+        let fees = [1, 2, 3];
+        assert_eq!(percentile(&fees, 34), 2);
+        assert_eq!(percentile(&fees, 33), 1);
+        assert_eq!(percentile(&fees, 67), 3);
+    }
+}
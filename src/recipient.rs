@@ -0,0 +1,257 @@
+use crate::amount::format_sol;
+use crate::token::{token_programs, unpack_token_account};
+use anyhow::Result;
+use clap::Args;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_program;
+
+// Overrides for the checks run on the recipient of a SOL transfer
+#[derive(Args, Clone, Debug, Default)]
+pub struct RecipientCheckArgs {
+    /// Send to an address that has no account yet, like the Solana CLI flag
+    #[arg(long)]
+    pub allow_unfunded_recipient: bool,
+    /// Send even when the recipient looks like a program, a program derived
+    /// address, a token account or a mint
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// Most accounts `getMultipleAccounts` returns per request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+impl RecipientCheckArgs {
+    /// Looks up `to` before `lamports` are sent to it and refuses recipients
+    /// that are most likely a mistake. Returns warnings about recipients let
+    /// through by `--force` or that are merely unusual
+    pub async fn check_recipient(
+        &self,
+        rpc_client: &RpcClient,
+        to: &Pubkey,
+        lamports: u64,
+    ) -> Result<Vec<String>> {
+        let account = rpc_client
+            .get_account_with_commitment(to, rpc_client.commitment())
+            .await?
+            .value;
+        let minimum = rpc_client.get_minimum_balance_for_rent_exemption(0).await?;
+        self.check_account(to, account.as_ref(), lamports, minimum)
+    }
+
+    /// `check_recipient` for many transfers, looking the recipients up in
+    /// bulk. Returns the outcome of every transfer in order
+    pub async fn check_recipients(
+        &self,
+        rpc_client: &RpcClient,
+        transfers: &[(Pubkey, u64)],
+    ) -> Result<Vec<Result<Vec<String>>>> {
+        let minimum = rpc_client.get_minimum_balance_for_rent_exemption(0).await?;
+        let mut outcomes = Vec::new();
+        for chunk in transfers.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let addresses = chunk.iter().map(|(to, _)| *to).collect::<Vec<_>>();
+            let accounts = rpc_client
+                .get_multiple_accounts_with_commitment(&addresses, rpc_client.commitment())
+                .await?
+                .value;
+            for ((to, lamports), account) in chunk.iter().zip(&accounts) {
+                outcomes.push(self.check_account(to, account.as_ref(), *lamports, minimum));
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// The checks of `check_recipient` on an account already looked up,
+    /// `minimum` being the rent-exempt minimum of a new account
+    fn check_account(
+        &self,
+        to: &Pubkey,
+        account: Option<&Account>,
+        lamports: u64,
+        minimum: u64,
+    ) -> Result<Vec<String>> {
+        let mut problems = Vec::new();
+        let mut warnings = Vec::new();
+        if !to.is_on_curve() {
+            problems.push(format!(
+                "{} is off-curve, a program derived address no one holds the key to",
+                to
+            ));
+        }
+        match account {
+            None => {
+                if !self.allow_unfunded_recipient {
+                    anyhow::bail!(
+                        "{} has no account yet, double check the address and pass `--allow-unfunded-recipient` to fund it",
+                        to
+                    );
+                }
+                if lamports < minimum {
+                    problems.push(format!(
+                        "{} SOL would leave the new account {} below the {} SOL rent-exempt minimum, the transfer would fail",
+                        format_sol(lamports),
+                        to,
+                        format_sol(minimum)
+                    ));
+                }
+            }
+            Some(account) if account.executable => {
+                problems.push(format!("{} is an executable program", to));
+            }
+            Some(account) if token_programs().contains(&account.owner) => {
+                match unpack_token_account(to, &account.data) {
+                    Ok(token_account) => problems.push(format!(
+                        "{} is a token account of the wallet {}, send SOL to the wallet itself or use `wrap` for wSOL",
+                        to, token_account.owner
+                    )),
+                    Err(_) => problems.push(format!("{} is a token mint", to)),
+                }
+            }
+            Some(account) if account.owner != system_program::id() => {
+                warnings.push(format!(
+                    "{} is owned by the program {}, not a wallet",
+                    to, account.owner
+                ));
+            }
+            Some(_) => {}
+        }
+        if problems.is_empty() {
+            return Ok(warnings);
+        }
+        if !self.force {
+            anyhow::bail!("{}, pass `--force` to send anyway", problems.join("; "));
+        }
+        problems.extend(warnings);
+        Ok(problems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::program_pack::Pack;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use spl_token_2022::state::{Account as TokenAccount, AccountState};
+
+    const MINIMUM: u64 = 890_880;
+
+    fn checks(allow_unfunded_recipient: bool, force: bool) -> RecipientCheckArgs {
+        RecipientCheckArgs {
+            allow_unfunded_recipient,
+            force,
+        }
+    }
+
+    fn account(owner: Pubkey, data: Vec<u8>, executable: bool) -> Account {
+        Account {
+            lamports: 1_000_000,
+            data,
+            owner,
+            executable,
+            rent_epoch: 0,
+        }
+    }
+
+    fn token_account(owner: &Pubkey) -> Account {
+        let mut data = vec![0; TokenAccount::LEN];
+        let state = TokenAccount {
+            mint: Pubkey::new_unique(),
+            owner: *owner,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        };
+        TokenAccount::pack(state, &mut data).unwrap();
+        account(spl_token::id(), data, false)
+    }
+
+    fn check(
+        checks: RecipientCheckArgs,
+        to: &Pubkey,
+        account: Option<Account>,
+    ) -> Result<Vec<String>> {
+        checks.check_account(to, account.as_ref(), 1_000_000, MINIMUM)
+    }
+
+    #[test]
+    fn wallets_pass_without_warnings() {
+        let to = Keypair::new().pubkey();
+        let wallet = account(system_program::id(), Vec::new(), false);
+        assert!(check(checks(false, false), &to, Some(wallet))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn unfunded_recipients_need_the_flag() {
+        let to = Keypair::new().pubkey();
+        let err = check(checks(false, false), &to, None).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("pass `--allow-unfunded-recipient`"));
+        // Not even `--force` funds a new account by accident
+        assert!(check(checks(false, true), &to, None).is_err());
+        assert!(check(checks(true, false), &to, None).unwrap().is_empty());
+
+        let err = checks(true, false)
+            .check_account(&to, None, MINIMUM - 1, MINIMUM)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("below the 0.00089088 SOL rent-exempt minimum"));
+    }
+
+    #[test]
+    fn likely_mistakes_are_refused_unless_forced() {
+        let wallet = Keypair::new().pubkey();
+        let (pda, _) = Pubkey::find_program_address(&[b"vault"], &Pubkey::new_unique());
+        let funded = || Some(account(system_program::id(), Vec::new(), false));
+        let cases = [
+            (pda, funded(), "is off-curve"),
+            (
+                wallet,
+                Some(account(Pubkey::new_unique(), Vec::new(), true)),
+                "is an executable program",
+            ),
+            (
+                Keypair::new().pubkey(),
+                Some(token_account(&wallet)),
+                "is a token account of the wallet",
+            ),
+            (
+                Keypair::new().pubkey(),
+                Some(account(spl_token::id(), vec![0; 82], false)),
+                "is a token mint",
+            ),
+        ];
+        for (to, account, problem) in cases {
+            let err = check(checks(false, false), &to, account.clone()).unwrap_err();
+            assert!(err.to_string().contains(problem), "{}", err);
+            assert!(err.to_string().ends_with("pass `--force` to send anyway"));
+
+            let warnings = check(checks(false, true), &to, account).unwrap();
+            assert_eq!(warnings.len(), 1);
+            assert!(warnings[0].contains(problem));
+        }
+    }
+
+    #[test]
+    fn accounts_of_other_programs_only_warn() {
+        let to = Keypair::new().pubkey();
+        let owner = Pubkey::new_unique();
+        let warnings = check(
+            checks(false, false),
+            &to,
+            Some(account(owner, vec![1, 2, 3], false)),
+        )
+        .unwrap();
+        assert_eq!(
+            warnings,
+            [format!(
+                "{} is owned by the program {}, not a wallet",
+                to, owner
+            )]
+        );
+    }
+}
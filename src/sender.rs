@@ -0,0 +1,495 @@
+use crate::output::OutputFormat;
+use anyhow::{Context, Result};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde::Serialize;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_client::rpc_custom_error::JSON_RPC_SERVER_ERROR_SEND_TRANSACTION_PREFLIGHT_FAILURE;
+use solana_client::rpc_request::RpcError;
+use solana_sdk::clock::MAX_PROCESSING_AGE;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::signer::Signer;
+use solana_sdk::signers::Signers;
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const REBROADCAST_INTERVAL: Duration = Duration::from_secs(2);
+/// How many times an expired transaction is re-signed with a fresh blockhash
+const MAX_RESIGNS: usize = 3;
+/// How many polls in a row may fail, e.g on a rate limit, before giving up on
+/// a transaction that may still be in flight
+const MAX_FAILED_POLLS: usize = 20;
+/// How long a transaction without a block height to expire at may go unseen,
+/// e.g after the fork it landed on was dropped
+const UNSEEN_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// The confirmation stage a signature reached
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Sent,
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl Stage {
+    fn progress(&self) -> u64 {
+        match self {
+            Stage::Sent => 25,
+            Stage::Processed => 50,
+            Stage::Confirmed => 75,
+            Stage::Finalized => 100,
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        match self {
+            Stage::Sent => "Transaction sent, waiting to be processed...",
+            Stage::Processed => "Transaction processed, waiting for confirmation...",
+            Stage::Confirmed => "Transaction confirmed, waiting to be finalized...",
+            Stage::Finalized => "Transaction finalized",
+        }
+    }
+
+    fn target(commitment: CommitmentConfig) -> Stage {
+        match commitment.commitment {
+            CommitmentLevel::Processed => Stage::Processed,
+            CommitmentLevel::Confirmed => Stage::Confirmed,
+            CommitmentLevel::Finalized => Stage::Finalized,
+        }
+    }
+}
+
+impl From<&TransactionConfirmationStatus> for Stage {
+    fn from(status: &TransactionConfirmationStatus) -> Self {
+        match status {
+            TransactionConfirmationStatus::Processed => Stage::Processed,
+            TransactionConfirmationStatus::Confirmed => Stage::Confirmed,
+            TransactionConfirmationStatus::Finalized => Stage::Finalized,
+        }
+    }
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stage = match self {
+            Stage::Sent => "sent",
+            Stage::Processed => "processed",
+            Stage::Confirmed => "confirmed",
+            Stage::Finalized => "finalized",
+        };
+        write!(f, "{}", stage)
+    }
+}
+
+/// Where a sent transaction ended up
+#[derive(Clone, Debug)]
+pub struct Confirmation {
+    pub signature: Signature,
+    pub slot: u64,
+    pub status: Stage,
+    /// How many times the transaction was re-signed after its blockhash expired
+    pub resigned: usize,
+}
+
+/// A transaction that definitely did not land, as opposed to errors like
+/// losing the RPC node that leave it unknown
+#[derive(Debug)]
+pub enum NotLanded {
+    /// The RPC node refused it in preflight, so it was never broadcast
+    Rejected(ClientError),
+    /// It was processed but failed, only the fee was charged
+    Failed(Signature, TransactionError),
+    /// Every attempt expired without any signature being seen on chain
+    Expired(Signature, usize),
+}
+
+impl fmt::Display for NotLanded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotLanded::Rejected(err) => write!(f, "{}", err),
+            NotLanded::Failed(signature, err) => {
+                write!(f, "Transaction {} failed: {}", signature, err)
+            }
+            NotLanded::Expired(signature, attempts) => write!(
+                f,
+                "Transaction {} expired before it was processed, gave up after {} attempts",
+                signature, attempts
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NotLanded {}
+
+/// Whether `error` says the transaction definitely did not land
+pub fn did_not_land(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<NotLanded>().is_some()
+}
+
+/// Whether `error` is the RPC node refusing a transaction in preflight, in
+/// which case it was never broadcast
+pub fn is_preflight_failure(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<NotLanded>(),
+        Some(NotLanded::Rejected(_))
+    )
+}
+
+/// `signers` without repeats, for when the fee payer is also an authority
+pub fn unique_signers<'a>(signers: &[&'a dyn Signer]) -> Vec<&'a dyn Signer> {
+    let mut unique: Vec<&dyn Signer> = Vec::new();
+    for signer in signers {
+        if !unique.iter().any(|s| s.pubkey() == signer.pubkey()) {
+            unique.push(*signer);
+        }
+    }
+    unique
+}
+
+/// The progress bar used while sending a transaction, hidden for
+/// machine-readable output
+pub fn new_progress_bar(output: OutputFormat) -> ProgressBar {
+    let progress_bar = ProgressBar::new(100);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos:>7}% {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("#>-"),
+    );
+    if output != OutputFormat::Human {
+        progress_bar.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    progress_bar.enable_steady_tick(Duration::from_millis(100));
+    progress_bar
+}
+
+/// Signs `instructions` with a recent blockhash, sends them and follows the
+/// signature until it reaches the client's commitment. The transaction is
+/// re-broadcast while it is pending, and re-signed with a fresh blockhash
+/// when the old one expires without the transaction having landed.
+pub async fn send_and_confirm<T: Signers + ?Sized>(
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    signers: &T,
+    progress_bar: &ProgressBar,
+) -> Result<Confirmation> {
+    send_and_confirm_recorded(
+        rpc_client,
+        instructions,
+        payer,
+        signers,
+        progress_bar,
+        &mut |_, _| Ok(()),
+    )
+    .await
+}
+
+/// `send_and_confirm`, calling `on_signed` with every signature and the last
+/// block height it can land at before the transaction is sent, so a caller
+/// that is interrupted can later tell whether it landed
+pub async fn send_and_confirm_recorded<T: Signers + ?Sized>(
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    signers: &T,
+    progress_bar: &ProgressBar,
+    on_signed: &mut dyn FnMut(&Signature, u64) -> Result<()>,
+) -> Result<Confirmation> {
+    let commitment = rpc_client.commitment();
+    let target = Stage::target(commitment);
+    let mut resigned = 0;
+    loop {
+        progress_bar.set_message("Signing transaction...");
+        let (blockhash, last_valid_block_height) = rpc_client
+            .get_latest_blockhash_with_commitment(commitment)
+            .await
+            .context("Failed to fetch a recent blockhash")?;
+        let transaction =
+            Transaction::new_signed_with_payer(instructions, Some(payer), signers, blockhash);
+        on_signed(&transaction.signatures[0], last_valid_block_height)?;
+
+        // The first send runs preflight checks so invalid transactions fail
+        // right away, re-broadcasts skip them
+        let signature = rpc_client
+            .send_transaction_with_config(
+                &transaction,
+                RpcSendTransactionConfig {
+                    preflight_commitment: Some(commitment.commitment),
+                    max_retries: Some(0),
+                    ..RpcSendTransactionConfig::default()
+                },
+            )
+            .await
+            .map_err(|err| match &err.kind {
+                ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                    code: JSON_RPC_SERVER_ERROR_SEND_TRANSACTION_PREFLIGHT_FAILURE,
+                    ..
+                }) => anyhow::Error::new(NotLanded::Rejected(err)),
+                _ => err.into(),
+            })?;
+        set_stage(progress_bar, Stage::Sent);
+
+        let tracker = Tracker {
+            rpc_client,
+            transaction: Some(&transaction),
+            signature,
+            target,
+            progress_bar,
+        };
+        if let Some((slot, status)) = tracker.wait(Some(last_valid_block_height)).await? {
+            return Ok(Confirmation {
+                signature,
+                slot,
+                status,
+                resigned,
+            });
+        }
+
+        // The blockhash expired, re-signing is only safe once the cluster has
+        // no record of the old signature at all
+        let landed = rpc_client
+            .get_signature_statuses_with_history(&[signature])
+            .await?
+            .value
+            .into_iter()
+            .next()
+            .flatten();
+        if landed.is_some() {
+            if let Some((slot, status)) = tracker.wait(None).await? {
+                return Ok(Confirmation {
+                    signature,
+                    slot,
+                    status,
+                    resigned,
+                });
+            }
+        }
+        if resigned == MAX_RESIGNS {
+            progress_bar.abandon_with_message("Transaction expired");
+            return Err(NotLanded::Expired(signature, MAX_RESIGNS + 1).into());
+        }
+        resigned += 1;
+        progress_bar.set_position(0);
+        progress_bar.set_message(format!(
+            "Blockhash expired, re-signing (attempt {} of {})...",
+            resigned + 1,
+            MAX_RESIGNS + 1
+        ));
+    }
+}
+
+/// Waits for a transaction signed and sent by someone else, e.g the faucet,
+/// to reach the client's commitment
+pub async fn confirm_signature(
+    rpc_client: &RpcClient,
+    signature: Signature,
+    progress_bar: &ProgressBar,
+) -> Result<Confirmation> {
+    set_stage(progress_bar, Stage::Sent);
+    // The sender's blockhash is unknown, but it can be at most
+    // `MAX_PROCESSING_AGE` blocks old, so the signature is dead after that
+    let block_height = rpc_client.get_block_height().await?;
+    let tracker = Tracker {
+        rpc_client,
+        transaction: None,
+        signature,
+        target: Stage::target(rpc_client.commitment()),
+        progress_bar,
+    };
+    match tracker
+        .wait(Some(block_height + MAX_PROCESSING_AGE as u64))
+        .await?
+    {
+        Some((slot, status)) => Ok(Confirmation {
+            signature,
+            slot,
+            status,
+            resigned: 0,
+        }),
+        None => {
+            progress_bar.abandon_with_message("Transaction expired");
+            anyhow::bail!("Transaction {} expired before it was processed", signature)
+        }
+    }
+}
+
+/// Follows one signed transaction through the confirmation stages
+struct Tracker<'a> {
+    rpc_client: &'a RpcClient,
+    /// Re-broadcast while pending, when we are the ones who sent it
+    transaction: Option<&'a Transaction>,
+    signature: Signature,
+    target: Stage,
+    progress_bar: &'a ProgressBar,
+}
+
+/// What one poll of the signature found
+enum Poll {
+    Seen(TransactionStatus),
+    Pending,
+    /// The block height passed the last one the transaction could land at
+    Expired,
+}
+
+impl Tracker<'_> {
+    /// Polls the signature until it reaches the target stage, returning its
+    /// slot and stage, or `None` once the block height passes
+    /// `last_valid_block_height` without the transaction being seen. Without
+    /// a block height it gives up once the signature goes unseen for too long.
+    async fn wait(&self, last_valid_block_height: Option<u64>) -> Result<Option<(u64, Stage)>> {
+        let mut last_broadcast = Instant::now();
+        let mut last_seen = Instant::now();
+        let mut failed_polls = 0;
+        let mut stage = Stage::Sent;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let poll = match self.poll(last_valid_block_height).await {
+                Ok(poll) => {
+                    failed_polls = 0;
+                    poll
+                }
+                Err(_) if failed_polls < MAX_FAILED_POLLS => {
+                    failed_polls += 1;
+                    continue;
+                }
+                Err(err) => {
+                    self.progress_bar.abandon_with_message("Lost the RPC node");
+                    return Err(err.context(format!(
+                        "Lost contact with the RPC node while waiting for transaction {}, it may still land",
+                        self.signature
+                    )));
+                }
+            };
+            let status = match poll {
+                Poll::Seen(status) => status,
+                Poll::Expired => return Ok(None),
+                Poll::Pending => {
+                    if last_valid_block_height.is_none() && last_seen.elapsed() >= UNSEEN_TIMEOUT {
+                        self.progress_bar
+                            .abandon_with_message("Transaction no longer seen");
+                        anyhow::bail!(
+                            "Transaction {} was not seen for {} seconds, it may have been dropped with its fork",
+                            self.signature,
+                            UNSEEN_TIMEOUT.as_secs()
+                        );
+                    }
+                    if let Some(transaction) = self
+                        .transaction
+                        .filter(|_| last_broadcast.elapsed() >= REBROADCAST_INTERVAL)
+                    {
+                        // Errors here are only transient, the status poll is what
+                        // decides the outcome
+                        let _ = self
+                            .rpc_client
+                            .send_transaction_with_config(
+                                transaction,
+                                RpcSendTransactionConfig {
+                                    skip_preflight: true,
+                                    max_retries: Some(0),
+                                    ..RpcSendTransactionConfig::default()
+                                },
+                            )
+                            .await;
+                        last_broadcast = Instant::now();
+                    }
+                    continue;
+                }
+            };
+            last_seen = Instant::now();
+            if let Some(err) = status.err {
+                self.progress_bar.abandon_with_message("Transaction failed");
+                return Err(NotLanded::Failed(self.signature, err).into());
+            }
+            let reached = status
+                .confirmation_status
+                .as_ref()
+                .map(Stage::from)
+                .unwrap_or(Stage::Processed);
+            if reached > stage {
+                stage = reached;
+                set_stage(self.progress_bar, stage);
+            }
+            if stage >= self.target {
+                self.progress_bar
+                    .finish_with_message("Transaction completed");
+                return Ok(Some((status.slot, stage)));
+            }
+        }
+    }
+
+    /// Looks the signature up once. Without a block height to expire at the
+    /// transaction may be older than the recent status cache, so the whole
+    /// history is searched.
+    async fn poll(&self, last_valid_block_height: Option<u64>) -> Result<Poll> {
+        let signatures = [self.signature];
+        let statuses = match last_valid_block_height {
+            Some(_) => self.rpc_client.get_signature_statuses(&signatures).await?,
+            None => {
+                self.rpc_client
+                    .get_signature_statuses_with_history(&signatures)
+                    .await?
+            }
+        };
+        if let Some(status) = statuses.value.into_iter().next().flatten() {
+            return Ok(Poll::Seen(status));
+        }
+        if let Some(last_valid_block_height) = last_valid_block_height {
+            let block_height = self
+                .rpc_client
+                .get_block_height_with_commitment(self.rpc_client.commitment())
+                .await?;
+            if block_height > last_valid_block_height {
+                return Ok(Poll::Expired);
+            }
+        }
+        Ok(Poll::Pending)
+    }
+}
+
+fn set_stage(progress_bar: &ProgressBar, stage: Stage) {
+    progress_bar.set_position(stage.progress());
+    progress_bar.set_message(stage.message());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_definitive_failures_apart() {
+        let signature = Signature::new_unique();
+        let failed = anyhow::Error::new(NotLanded::Failed(
+            signature,
+            TransactionError::InsufficientFundsForFee,
+        ))
+        .context("Failed to send the transfer");
+        assert!(did_not_land(&failed));
+        assert!(!is_preflight_failure(&failed));
+
+        let expired = anyhow::Error::new(NotLanded::Expired(signature, MAX_RESIGNS + 1));
+        assert_eq!(
+            expired.to_string(),
+            format!(
+                "Transaction {} expired before it was processed, gave up after 4 attempts",
+                signature
+            )
+        );
+        assert!(did_not_land(&expired));
+
+        let lost = anyhow::anyhow!("RPC node unreachable").context(format!(
+            "Lost contact with the RPC node while waiting for transaction {}, it may still land",
+            signature
+        ));
+        assert!(!did_not_land(&lost));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::keystore::read_keypair;
+use crate::mnemonic::{
+    keypair_from_mnemonic, parse_derivation_path, read_secret, DEFAULT_DERIVATION_PATH,
+};
+use anyhow::{Context, Result};
+use solana_sdk::signature::Keypair;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Where a keypair comes from, parsed from a signer URI
+#[derive(Clone, Debug, PartialEq)]
+pub enum SignerSource {
+    /// A plain or encrypted keypair file, `file:<path>` or a bare path
+    File(PathBuf),
+    /// A secret piped through stdin, `stdin:`
+    Stdin,
+    /// A seed phrase or base58 secret typed interactively, `prompt:`
+    Prompt,
+    /// A secret held by an environment variable, `env:<VAR>`
+    Env(String),
+    /// A base58 secret key as exported by browser wallets
+    Base58(String),
+}
+
+impl SignerSource {
+    /// The keypair file, for sources that are one
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            SignerSource::File(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn keypair(&self) -> Result<Keypair> {
+        match self {
+            SignerSource::File(path) => read_keypair(path),
+            SignerSource::Stdin => {
+                let mut secret = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut secret)
+                    .context("Failed to read the secret from stdin")?;
+                keypair_from_secret(&secret).context("Invalid secret on stdin")
+            }
+            SignerSource::Prompt => {
+                let secret = read_secret("Seed phrase or base58 secret key: ")?;
+                keypair_from_secret(&secret).context("Invalid secret")
+            }
+            SignerSource::Env(var) => {
+                let secret = std::env::var(var)
+                    .with_context(|| format!("Environment variable `{}` is not set", var))?;
+                keypair_from_secret(&secret)
+                    .with_context(|| format!("Invalid secret in environment variable `{}`", var))
+            }
+            SignerSource::Base58(secret) => keypair_from_base58(secret),
+        }
+    }
+}
+
+/// Parses a JSON byte array, a base58 secret key or a seed phrase, the
+/// latter derived with the default derivation path
+fn keypair_from_secret(secret: &str) -> Result<Keypair> {
+    let secret = secret.trim();
+    if secret.starts_with('[') {
+        let bytes: Vec<u8> =
+            serde_json::from_str(secret).context("Invalid JSON keypair byte array")?;
+        return Keypair::from_bytes(&bytes).map_err(|e| anyhow::anyhow!("Invalid keypair: {}", e));
+    }
+    if secret.split_whitespace().count() > 1 {
+        let derivation_path = parse_derivation_path(DEFAULT_DERIVATION_PATH)?;
+        return keypair_from_mnemonic(secret, "", Some(derivation_path));
+    }
+    keypair_from_base58(secret)
+}
+
+fn keypair_from_base58(secret: &str) -> Result<Keypair> {
+    let bytes = bs58::decode(secret)
+        .into_vec()
+        .context("Invalid base58 secret key")?;
+    Keypair::from_bytes(&bytes).map_err(|e| anyhow::anyhow!("Invalid base58 secret key: {}", e))
+}
+
+/// A base58 string that decodes to the 64 bytes of a keypair
+fn is_base58_keypair(s: &str) -> bool {
+    bs58::decode(s)
+        .into_vec()
+        .is_ok_and(|bytes| bytes.len() == 64)
+}
+
+impl FromStr for SignerSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(path) = s.strip_prefix("file:") {
+            return Ok(SignerSource::File(PathBuf::from(path)));
+        }
+        // An existing file always wins, over bare names and base58 alike
+        let exists = Path::new(s).exists();
+        if s == "stdin:" || (s == "stdin" && !exists) {
+            return Ok(SignerSource::Stdin);
+        }
+        if s == "prompt:" || (s == "prompt" && !exists) {
+            return Ok(SignerSource::Prompt);
+        }
+        if let Some(var) = s.strip_prefix("env:") {
+            if var.is_empty() {
+                anyhow::bail!("Missing environment variable name in `{}`", s);
+            }
+            return Ok(SignerSource::Env(var.to_string()));
+        }
+        if !exists && is_base58_keypair(s) {
+            return Ok(SignerSource::Base58(s.to_string()));
+        }
+        Ok(SignerSource::File(PathBuf::from(s)))
+    }
+}
+
+impl std::fmt::Display for SignerSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignerSource::File(path) => write!(f, "{}", path.display()),
+            SignerSource::Stdin => write!(f, "stdin:"),
+            SignerSource::Prompt => write!(f, "prompt:"),
+            SignerSource::Env(var) => write!(f, "env:{}", var),
+            // Never echo the secret itself
+            SignerSource::Base58(_) => write!(f, "<base58 secret key>"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signer::Signer;
+
+    #[test]
+    fn parses_signer_uris() {
+        let parse = |s: &str| s.parse::<SignerSource>().unwrap();
+        assert_eq!(
+            parse("file:~/id.json"),
+            SignerSource::File(PathBuf::from("~/id.json"))
+        );
+        assert_eq!(
+            parse("id.json"),
+            SignerSource::File(PathBuf::from("id.json"))
+        );
+        assert_eq!(parse("stdin:"), SignerSource::Stdin);
+        assert_eq!(parse("stdin"), SignerSource::Stdin);
+        assert_eq!(parse("prompt:"), SignerSource::Prompt);
+        assert_eq!(parse("prompt"), SignerSource::Prompt);
+        assert_eq!(parse("env:MY_KEY"), SignerSource::Env("MY_KEY".to_string()));
+        assert!("env:".parse::<SignerSource>().is_err());
+    }
+
+    #[test]
+    fn parses_base58_secret_keys() {
+        let secret = Keypair::new().to_base58_string();
+        assert_eq!(
+            secret.parse::<SignerSource>().unwrap(),
+            SignerSource::Base58(secret.clone())
+        );
+        // A base58 address is 32 bytes, not a keypair, so it stays a path
+        let address = Keypair::new().pubkey().to_string();
+        assert_eq!(
+            address.parse::<SignerSource>().unwrap(),
+            SignerSource::File(PathBuf::from(&address))
+        );
+    }
+
+    #[test]
+    fn existing_files_win_over_base58() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let secret = Keypair::new().to_base58_string();
+        let path = dir.path().join(&secret);
+        std::fs::write(&path, "[]").unwrap();
+        let source = path.to_str().unwrap().parse::<SignerSource>().unwrap();
+        assert_eq!(source, SignerSource::File(path.clone()));
+        assert_eq!(source.path(), Some(path.as_path()));
+    }
+
+    #[test]
+    fn display_hides_secrets() {
+        let secret = Keypair::new().to_base58_string();
+        let source = SignerSource::Base58(secret.clone());
+        assert!(!source.to_string().contains(&secret));
+        assert_eq!(SignerSource::Env("KEY".to_string()).to_string(), "env:KEY");
+    }
+
+    #[test]
+    fn reads_every_secret_format() {
+        let keypair = Keypair::new();
+        let json = serde_json::to_string(&keypair.to_bytes().to_vec()).unwrap();
+        let base58 = keypair.to_base58_string();
+        assert_eq!(keypair_from_secret(&json).unwrap(), keypair);
+        assert_eq!(
+            keypair_from_secret(&format!(" {}\n", base58)).unwrap(),
+            keypair
+        );
+        assert!(keypair_from_secret("[1, 2, 3]").is_err());
+        assert!(keypair_from_secret("not base58 0OIl").is_err());
+    }
+
+    #[test]
+    fn reads_seed_phrases_with_the_default_path() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon \
+                      abandon abandon abandon about";
+        let derivation_path = parse_derivation_path(DEFAULT_DERIVATION_PATH).unwrap();
+        let expected = keypair_from_mnemonic(phrase, "", Some(derivation_path)).unwrap();
+        assert_eq!(keypair_from_secret(phrase).unwrap(), expected);
+    }
+
+    #[test]
+    fn reads_secrets_from_the_environment() {
+        let keypair = Keypair::new();
+        // Only this test sets the variable, so parallel tests cannot race on it
+        let var = "SOL_DASH_TEST_READS_SECRETS_FROM_THE_ENVIRONMENT";
+        std::env::set_var(var, keypair.to_base58_string());
+        let source = format!("env:{}", var).parse::<SignerSource>().unwrap();
+        assert_eq!(source.keypair().unwrap(), keypair);
+    }
+
+    #[test]
+    fn reports_unset_environment_variables() {
+        let source = SignerSource::Env("SOL_DASH_TEST_UNSET_SIGNER_SECRET".to_string());
+        let err = source.keypair().unwrap_err();
+        assert!(err.to_string().contains("is not set"));
+    }
+}
@@ -0,0 +1,420 @@
+use crate::amount::format_sol;
+use crate::args::get_rpc_client;
+use crate::config::Settings;
+use crate::output::{print_output, CommandOutput};
+use crate::policy::MainnetGuard;
+use crate::priority_fee::PriorityFeeArgs;
+use crate::recipient::RecipientCheckArgs;
+use crate::sender::{did_not_land, new_progress_bar, send_and_confirm_recorded};
+use crate::signer::SignerSource;
+use crate::token::parse_pubkey;
+use anyhow::Result;
+use clap::Args;
+use colored::*;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::system_instruction;
+use std::fmt;
+
+#[derive(Args, Clone, Debug)]
+pub struct SweepArgs {
+    /// The keypair files or signer URIs of the wallets to drain, repeat the
+    /// flag for several wallets, defaults to the keypair in your config
+    /// profile
+    #[arg(short, long)]
+    pub from: Vec<SignerSource>,
+    /// The wallet address that receives every balance
+    #[arg(short, long)]
+    pub to: String,
+    /// Leave the rent-exempt minimum in every wallet instead of emptying it
+    #[arg(long)]
+    pub keep_rent_exempt: bool,
+    /// Show what each wallet would send without broadcasting anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Send on mainnet without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+    #[command(flatten)]
+    pub recipient_checks: RecipientCheckArgs,
+    #[command(flatten)]
+    pub priority_fee: PriorityFeeArgs,
+}
+
+/// Lamports a wallet can send in a single transfer, which is its balance less
+/// the fee and `reserve`, along with the fee. `instructions` are those of the
+/// transaction with a placeholder amount, the fee does not depend on it.
+pub async fn sweepable_lamports(
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    from: &Pubkey,
+    recent_blockhash: &Hash,
+    reserve: u64,
+) -> Result<(u64, u64)> {
+    let message = Message::new_with_blockhash(instructions, Some(from), recent_blockhash);
+    let fee = rpc_client.get_fee_for_message(&message).await?;
+    let balance = rpc_client.get_balance(from).await?;
+    Ok((amount_after_fees(balance, fee, reserve)?, fee))
+}
+
+/// What is left of `balance` to send after the fee and `reserve`, nothing
+/// left is an error
+fn amount_after_fees(balance: u64, fee: u64, reserve: u64) -> Result<u64> {
+    let lamports = balance
+        .checked_sub(fee)
+        .and_then(|l| l.checked_sub(reserve))
+        .filter(|l| *l > 0);
+    match lamports {
+        Some(lamports) => Ok(lamports),
+        None if reserve > 0 => anyhow::bail!(
+            "Balance of {} SOL does not cover the {} SOL fee and the {} SOL rent-exempt reserve",
+            format_sol(balance),
+            format_sol(fee),
+            format_sol(reserve)
+        ),
+        None => anyhow::bail!(
+            "Balance of {} SOL does not cover the {} SOL fee",
+            format_sol(balance),
+            format_sol(fee)
+        ),
+    }
+}
+
+/// Lamports a system account keeps to stay rent exempt, when `keep` is set
+pub async fn rent_exempt_reserve(rpc_client: &RpcClient, keep: bool) -> Result<u64> {
+    if !keep {
+        return Ok(0);
+    }
+    Ok(rpc_client.get_minimum_balance_for_rent_exemption(0).await?)
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SweepStatus {
+    /// Drained by this run
+    Swept,
+    /// Left alone because its balance does not cover the fee
+    Skipped,
+    /// Sending the transfer failed
+    Failed,
+    /// Would be drained, for a dry run
+    Planned,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SweptWallet {
+    pub from: String,
+    /// Lamports sent to the destination, 0 unless swept or planned
+    pub lamports: u64,
+    /// Fee in lamports, including the priority fee
+    pub fee: u64,
+    pub status: SweepStatus,
+    pub signature: Option<String>,
+    /// Why the wallet was skipped or failed
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SweepReport {
+    pub to: String,
+    pub dry_run: bool,
+    pub wallets: Vec<SweptWallet>,
+    /// Lamports sent to the destination, or that would be for a dry run
+    pub lamports: u64,
+    /// Total fee in lamports of the sent transfers, estimated for a dry run
+    pub fee: u64,
+    pub failed: usize,
+}
+
+impl fmt::Display for SweepReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (verb, fee) = if self.dry_run {
+            ("Would sweep", "Estimated fee")
+        } else {
+            ("Swept", "Fee")
+        };
+        writeln!(
+            f,
+            "{} {} SOL into {}:",
+            verb,
+            format_sol(self.lamports).green().bold(),
+            self.to.blue()
+        )?;
+        for wallet in &self.wallets {
+            match wallet.status {
+                SweepStatus::Swept | SweepStatus::Planned => {
+                    write!(f, "  {} {} SOL", wallet.from, format_sol(wallet.lamports))?;
+                    if let Some(signature) = &wallet.signature {
+                        write!(f, " {}", signature.yellow())?;
+                    }
+                    writeln!(f)?;
+                }
+                SweepStatus::Skipped | SweepStatus::Failed => {
+                    let status = if wallet.status == SweepStatus::Failed {
+                        "failed".red().bold()
+                    } else {
+                        "skipped".normal()
+                    };
+                    writeln!(
+                        f,
+                        "  {} {}: {}",
+                        wallet.from,
+                        status,
+                        wallet.error.as_deref().unwrap_or_default()
+                    )?;
+                }
+            }
+        }
+        write!(f, "{}: {} SOL", fee, format_sol(self.fee))
+    }
+}
+
+/// A `SweptWallet` as a CSV record
+#[derive(Serialize)]
+struct SweptWalletRow<'a> {
+    to: &'a str,
+    dry_run: bool,
+    from: &'a str,
+    lamports: u64,
+    fee: u64,
+    status: SweepStatus,
+    signature: Option<&'a str>,
+    error: Option<&'a str>,
+}
+
+impl CommandOutput for SweepReport {
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        for wallet in &self.wallets {
+            writer.serialize(SweptWalletRow {
+                to: &self.to,
+                dry_run: self.dry_run,
+                from: &wallet.from,
+                lamports: wallet.lamports,
+                fee: wallet.fee,
+                status: wallet.status,
+                signature: wallet.signature.as_deref(),
+                error: wallet.error.as_deref(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl SweepArgs {
+    pub async fn sweep_handler(&self, settings: &Settings) -> Result<()> {
+        let rpc_client = get_rpc_client(settings)?;
+        self.sweep(&rpc_client, settings).await
+    }
+
+    async fn sweep(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        let to = parse_pubkey(&self.to, "destination")?;
+        let sources = if self.from.is_empty() {
+            vec![settings.keypair_or_default(&None)?]
+        } else {
+            self.from.clone()
+        };
+        let mut keypairs = Vec::new();
+        for source in &sources {
+            let keypair = source.keypair()?;
+            if keypair.pubkey() == to {
+                anyhow::bail!("{} is both a source and the destination", to);
+            }
+            if keypairs
+                .iter()
+                .any(|k: &Keypair| k.pubkey() == keypair.pubkey())
+            {
+                anyhow::bail!("{} is passed more than once", keypair.pubkey());
+            }
+            keypairs.push(keypair);
+        }
+
+        let progress_bar = new_progress_bar(settings.output);
+        let reserve = rent_exempt_reserve(rpc_client, self.keep_rent_exempt).await?;
+        // Every wallet is priced first, so the whole sweep is checked and
+        // confirmed before anything is sent
+        let mut wallets = Vec::new();
+        let mut compute_budgets = Vec::new();
+        for (i, keypair) in keypairs.iter().enumerate() {
+            let from = keypair.pubkey();
+            progress_bar.set_message(format!("Pricing wallet {} of {}...", i + 1, keypairs.len()));
+            let compute_budget = self
+                .priority_fee
+                .compute_budget(
+                    rpc_client,
+                    &[system_instruction::transfer(&from, &to, 0)],
+                    &from,
+                )
+                .await?;
+            let recent_blockhash = rpc_client.get_latest_blockhash().await?;
+            let sweepable = sweepable_lamports(
+                rpc_client,
+                &compute_budget.with_instructions(&[system_instruction::transfer(&from, &to, 0)]),
+                &from,
+                &recent_blockhash,
+                reserve,
+            )
+            .await;
+            let mut wallet = SweptWallet {
+                from: from.to_string(),
+                lamports: 0,
+                fee: 0,
+                status: SweepStatus::Planned,
+                signature: None,
+                error: None,
+            };
+            match sweepable {
+                Ok((lamports, fee)) => {
+                    wallet.lamports = lamports;
+                    wallet.fee = fee;
+                }
+                Err(e) => {
+                    wallet.status = SweepStatus::Skipped;
+                    wallet.error = Some(e.to_string());
+                }
+            }
+            wallets.push(wallet);
+            compute_budgets.push(compute_budget);
+        }
+
+        // Only the first transfer can fund a new destination, so the
+        // destination is checked with the first amount
+        if let Some(first) = wallets.iter().find(|w| w.status == SweepStatus::Planned) {
+            progress_bar.set_message("Checking recipient...");
+            let warnings = self
+                .recipient_checks
+                .check_recipient(rpc_client, &to, first.lamports)
+                .await?;
+            for warning in warnings {
+                progress_bar.suspend(|| eprintln!("Warning: {}", warning));
+            }
+        }
+        let guard = MainnetGuard::new(rpc_client, settings).await?;
+        for (keypair, wallet) in keypairs.iter().zip(&wallets) {
+            if wallet.status == SweepStatus::Planned {
+                guard.check(&keypair.pubkey(), &[(to, wallet.lamports)])?;
+            }
+        }
+
+        if !self.dry_run {
+            let mut summary = vec![format!("To: {}", to)];
+            for wallet in wallets.iter().filter(|w| w.status == SweepStatus::Planned) {
+                summary.push(format!(
+                    "From: {} {} SOL, fee {} SOL",
+                    wallet.from,
+                    format_sol(wallet.lamports),
+                    format_sol(wallet.fee)
+                ));
+            }
+            progress_bar.suspend(|| guard.confirm(&summary, self.yes))?;
+        }
+
+        for (i, (keypair, wallet)) in keypairs.iter().zip(wallets.iter_mut()).enumerate() {
+            if self.dry_run || wallet.status != SweepStatus::Planned {
+                continue;
+            }
+            let from = keypair.pubkey();
+            progress_bar.set_position(0);
+            progress_bar.set_message(format!(
+                "Sweeping wallet {} of {}...",
+                i + 1,
+                keypairs.len()
+            ));
+            let transfers = [(to, wallet.lamports)];
+            let instructions = compute_budgets[i]
+                .with_instructions(&[system_instruction::transfer(&from, &to, wallet.lamports)]);
+            let mut recorded = None;
+            let result = send_and_confirm_recorded(
+                rpc_client,
+                &instructions,
+                &from,
+                &[keypair],
+                &progress_bar,
+                &mut |signature, _| guard.record(&from, &transfers, &mut recorded, signature),
+            )
+            .await;
+            if let (Err(e), Some(signature)) = (&result, recorded) {
+                if did_not_land(e) {
+                    guard.forget(&signature)?;
+                }
+            }
+            match result {
+                Ok(confirmation) => {
+                    wallet.status = SweepStatus::Swept;
+                    wallet.signature = Some(confirmation.signature.to_string());
+                }
+                Err(e) => {
+                    wallet.lamports = 0;
+                    wallet.status = SweepStatus::Failed;
+                    wallet.error = Some(e.to_string());
+                }
+            }
+        }
+        progress_bar.finish_and_clear();
+
+        let sent = |wallet: &&SweptWallet| {
+            matches!(wallet.status, SweepStatus::Swept | SweepStatus::Planned)
+        };
+        let output = SweepReport {
+            to: to.to_string(),
+            dry_run: self.dry_run,
+            lamports: wallets.iter().filter(sent).map(|w| w.lamports).sum(),
+            fee: wallets.iter().filter(sent).map(|w| w.fee).sum(),
+            failed: wallets
+                .iter()
+                .filter(|w| w.status == SweepStatus::Failed)
+                .count(),
+            wallets,
+        };
+        print_output(&output, settings.output)?;
+        if output.failed > 0 {
+            anyhow::bail!("{} wallet(s) failed to sweep", output.failed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweeps_the_balance_less_the_fee() {
+        assert_eq!(
+            amount_after_fees(1_000_000_000, 5000, 0).unwrap(),
+            999_995_000
+        );
+        assert_eq!(amount_after_fees(5001, 5000, 0).unwrap(), 1);
+    }
+
+    #[test]
+    fn keeps_the_rent_exempt_reserve() {
+        assert_eq!(
+            amount_after_fees(1_000_000_000, 5000, 890_880).unwrap(),
+            999_104_120
+        );
+        assert_eq!(amount_after_fees(895_881, 5000, 890_880).unwrap(), 1);
+    }
+
+    #[test]
+    fn refuses_balances_that_leave_nothing_to_send() {
+        let err = amount_after_fees(5000, 5000, 0).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Balance of 0.000005 SOL does not cover the 0.000005 SOL fee"
+        );
+        assert!(amount_after_fees(4999, 5000, 0).is_err());
+
+        let err = amount_after_fees(895_880, 5000, 890_880).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Balance of 0.00089588 SOL does not cover the 0.000005 SOL fee and the 0.00089088 SOL rent-exempt reserve"
+        );
+        assert!(amount_after_fees(5000, 5000, 890_880).is_err());
+    }
+}
@@ -0,0 +1,1213 @@
+use crate::amount::{format_sol, format_token_amount, TokenAmount};
+use crate::args::get_rpc_client;
+use crate::config::Settings;
+use crate::memo::{memo_instruction, parse_memo};
+use crate::output::{format_fee, print_output, CommandOutput};
+use crate::policy::MainnetGuard;
+use crate::priority_fee::PriorityFeeArgs;
+use crate::sender::{new_progress_bar, send_and_confirm, unique_signers, Confirmation, Stage};
+use crate::signer::SignerSource;
+use anyhow::{Context, Result};
+use clap::{ArgGroup, Args, Subcommand, ValueEnum};
+use colored::*;
+use indicatif::ProgressBar;
+use serde::Serialize;
+use solana_account_decoder::parse_token::{TokenAccountType, UiAccountState, UiTokenAccount};
+use solana_account_decoder::UiAccountData;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_request::TokenAccountsFilter;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::system_instruction;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use spl_token_2022::extension::StateWithExtensions;
+use spl_token_2022::instruction::AuthorityType;
+use spl_token_2022::state::{Account, Mint};
+use std::fmt;
+use std::str::FromStr;
+
+/// The SPL Token and Token-2022 program ids
+pub fn token_programs() -> [Pubkey; 2] {
+    [spl_token::id(), spl_token_2022::id()]
+}
+
+pub fn program_name(program_id: &Pubkey) -> &'static str {
+    if *program_id == spl_token_2022::id() {
+        "token-2022"
+    } else {
+        "token"
+    }
+}
+
+/// A token account as returned by `getTokenAccountsByOwner`
+#[derive(Debug)]
+pub struct TokenAccount {
+    pub address: Pubkey,
+    pub program_id: Pubkey,
+    /// The account's lamports, its rent plus any wrapped SOL
+    pub lamports: u64,
+    pub info: UiTokenAccount,
+}
+
+impl TokenAccount {
+    pub fn is_frozen(&self) -> bool {
+        self.info.state == UiAccountState::Frozen
+    }
+
+    /// The raw token balance
+    pub fn amount(&self) -> Result<u64> {
+        parse_units(&self.info.token_amount.amount)
+    }
+}
+
+/// Parses a raw token amount as returned by the RPC node
+pub fn parse_units(amount: &str) -> Result<u64> {
+    amount
+        .parse()
+        .with_context(|| format!("Invalid token amount `{}` returned by the RPC node", amount))
+}
+
+/// Every SPL Token and Token-2022 account owned by `owner`, only those of
+/// `mint` when it is set
+pub async fn get_token_accounts(
+    rpc_client: &RpcClient,
+    owner: &Pubkey,
+    mint: Option<&Pubkey>,
+) -> Result<Vec<TokenAccount>> {
+    // The node works out the token program of a mint filter on its own
+    let filters = match mint {
+        Some(mint) => vec![TokenAccountsFilter::Mint(*mint)],
+        None => token_programs()
+            .into_iter()
+            .map(TokenAccountsFilter::ProgramId)
+            .collect(),
+    };
+    let mut accounts = Vec::new();
+    for filter in filters {
+        let keyed_accounts = rpc_client
+            .get_token_accounts_by_owner(owner, filter)
+            .await
+            .with_context(|| format!("Failed to fetch the token accounts of {}", owner))?;
+        for keyed_account in keyed_accounts {
+            let address = Pubkey::from_str(&keyed_account.pubkey).with_context(|| {
+                format!("Invalid token account address {}", keyed_account.pubkey)
+            })?;
+            let UiAccountData::Json(parsed) = keyed_account.account.data else {
+                anyhow::bail!("The RPC node did not parse token account {}", address);
+            };
+            let TokenAccountType::Account(info) = serde_json::from_value(parsed.parsed)
+                .with_context(|| format!("Failed to parse token account {}", address))?
+            else {
+                continue;
+            };
+            accounts.push(TokenAccount {
+                address,
+                program_id: Pubkey::from_str(&keyed_account.account.owner).with_context(|| {
+                    format!("Invalid owner program of token account {}", address)
+                })?,
+                lamports: keyed_account.account.lamports,
+                info,
+            });
+        }
+    }
+    Ok(accounts)
+}
+
+/// The parts of a mint needed to build instructions against it
+#[derive(Clone, Copy, Debug)]
+pub struct MintInfo {
+    pub program_id: Pubkey,
+    pub decimals: u8,
+    pub mint_authority: Option<Pubkey>,
+    pub freeze_authority: Option<Pubkey>,
+}
+
+/// Fetches `mint` and checks it belongs to one of the token programs. The
+/// base layout is shared, so Token-2022 parsing covers both programs.
+pub async fn get_mint(rpc_client: &RpcClient, mint: &Pubkey) -> Result<MintInfo> {
+    let account = rpc_client
+        .get_account(mint)
+        .await
+        .with_context(|| format!("Failed to fetch mint {}", mint))?;
+    if !token_programs().contains(&account.owner) {
+        anyhow::bail!(
+            "{} is not a token mint, it is owned by {}",
+            mint,
+            account.owner
+        );
+    }
+    let state = StateWithExtensions::<Mint>::unpack(&account.data)
+        .map_err(|e| anyhow::anyhow!("{} is not a token mint: {}", mint, e))?;
+    Ok(MintInfo {
+        program_id: account.owner,
+        decimals: state.base.decimals,
+        mint_authority: state.base.mint_authority.into(),
+        freeze_authority: state.base.freeze_authority.into(),
+    })
+}
+
+/// Unpacks `data`, the account data of a token account, without its extensions
+pub fn unpack_token_account(address: &Pubkey, data: &[u8]) -> Result<Account> {
+    if data.len() < Account::LEN {
+        anyhow::bail!("{} is not a token account", address);
+    }
+    let state = StateWithExtensions::<Account>::unpack(data)
+        .map_err(|e| anyhow::anyhow!("{} is not a token account: {}", address, e))?;
+    Ok(state.base)
+}
+
+/// The raw token balance held in `data`, the account data of a token account
+fn token_account_amount(address: &Pubkey, data: &[u8]) -> Result<u64> {
+    Ok(unpack_token_account(address, data)?.amount)
+}
+
+pub fn parse_pubkey(address: &str, what: &str) -> Result<Pubkey> {
+    Pubkey::from_str(address).with_context(|| format!("Invalid {} address: {}", what, address))
+}
+
+/// Fails unless `signer` is the current `kind` authority of `mint`
+fn check_authority(
+    current: Option<Pubkey>,
+    signer: &Pubkey,
+    mint: &Pubkey,
+    kind: AuthorityKind,
+) -> Result<()> {
+    match current {
+        None => anyhow::bail!("Mint {} has no {} authority", mint, kind),
+        Some(authority) if authority != *signer => anyhow::bail!(
+            "{} is not the {} authority of mint {}, {} is",
+            signer,
+            kind,
+            mint,
+            authority
+        ),
+        Some(_) => Ok(()),
+    }
+}
+
+/// The fee and confirmation of a sent token transaction
+pub struct SentTransaction {
+    pub fee: u64,
+    pub priority_fee: u64,
+    pub confirmation: Confirmation,
+}
+
+/// Instructions with their compute budget added and the fee they cost
+pub struct PricedTransaction {
+    pub instructions: Vec<Instruction>,
+    /// Total fee in lamports, including `priority_fee`
+    pub fee: u64,
+    pub priority_fee: u64,
+}
+
+/// Adds the compute budget to `instructions` and prices the resulting
+/// transaction for `payer`
+pub async fn price_token_transaction(
+    rpc_client: &RpcClient,
+    priority_fee: &PriorityFeeArgs,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+) -> Result<PricedTransaction> {
+    let compute_budget = priority_fee
+        .compute_budget(rpc_client, instructions, payer)
+        .await?;
+    let instructions = compute_budget.with_instructions(instructions);
+    let recent_blockhash = rpc_client.get_latest_blockhash().await?;
+    let message = Message::new_with_blockhash(&instructions, Some(payer), &recent_blockhash);
+    let fee = rpc_client.get_fee_for_message(&message).await?;
+    Ok(PricedTransaction {
+        instructions,
+        fee,
+        priority_fee: compute_budget.priority_fee(),
+    })
+}
+
+/// Adds the compute budget to `instructions`, then signs, sends and confirms
+/// them with `payer` paying the fee
+pub async fn send_token_transaction(
+    rpc_client: &RpcClient,
+    priority_fee: &PriorityFeeArgs,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&dyn Signer],
+    progress_bar: &ProgressBar,
+) -> Result<SentTransaction> {
+    let priced =
+        price_token_transaction(rpc_client, priority_fee, instructions, &payer.pubkey()).await?;
+    send_priced_token_transaction(rpc_client, priced, payer, signers, progress_bar).await
+}
+
+/// Signs, sends and confirms a transaction priced by
+/// `price_token_transaction`, for callers that show the fee before sending
+pub async fn send_priced_token_transaction(
+    rpc_client: &RpcClient,
+    priced: PricedTransaction,
+    payer: &Keypair,
+    signers: &[&dyn Signer],
+    progress_bar: &ProgressBar,
+) -> Result<SentTransaction> {
+    let mut all_signers: Vec<&dyn Signer> = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let confirmation = send_and_confirm(
+        rpc_client,
+        &priced.instructions,
+        &payer.pubkey(),
+        &unique_signers(&all_signers),
+        progress_bar,
+    )
+    .await?;
+    Ok(SentTransaction {
+        fee: priced.fee,
+        priority_fee: priced.priority_fee,
+        confirmation,
+    })
+}
+
+/// Writes the fee, status and signature lines shared by the token outputs
+pub fn write_confirmation(
+    f: &mut fmt::Formatter<'_>,
+    fee: u64,
+    priority_fee: u64,
+    status: Stage,
+    slot: u64,
+    resigned: usize,
+) -> fmt::Result {
+    writeln!(f, "Fee: {}", format_fee(fee, priority_fee))?;
+    writeln!(f, "Status: {} in slot {}", status, slot)?;
+    if resigned > 0 {
+        writeln!(
+            f,
+            "Re-signed {} time(s) after the blockhash expired",
+            resigned
+        )?;
+    }
+    Ok(())
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct TokenArgs {
+    #[command(subcommand)]
+    pub command: TokenCommands,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum TokenCommands {
+    /// Transfer tokens to a wallet, creating its token account when needed
+    Transfer(TokenTransferArgs),
+    /// Create a new token mint
+    CreateMint(CreateMintArgs),
+    /// Mint new tokens to a wallet, creating its token account when needed
+    Mint(MintArgs),
+    /// Burn tokens held by a wallet
+    Burn(BurnArgs),
+    /// Change or disable the mint or freeze authority of a mint
+    SetAuthority(SetAuthorityArgs),
+    /// Freeze a token account so its tokens cannot be moved
+    Freeze(FreezeArgs),
+    /// Thaw a frozen token account
+    Thaw(FreezeArgs),
+}
+
+// Fee payer and compute budget flags of the token commands
+#[derive(Args, Clone, Debug)]
+pub struct TokenTxArgs {
+    /// Keypair file or signer URI paying the fees and any rent, defaults to
+    /// the authority signing the command
+    #[arg(long)]
+    pub fee_payer: Option<SignerSource>,
+    #[command(flatten)]
+    pub priority_fee: PriorityFeeArgs,
+}
+
+impl TokenTxArgs {
+    fn fee_payer(&self) -> Result<Option<Keypair>> {
+        self.fee_payer.as_ref().map(|s| s.keypair()).transpose()
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CreateMintArgs {
+    /// Number of decimal places of the token
+    #[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u8).range(0..=19))]
+    pub decimals: u8,
+    /// Address allowed to mint new tokens, defaults to the fee payer
+    #[arg(long)]
+    pub mint_authority: Option<String>,
+    /// Address allowed to freeze token accounts, the mint cannot freeze
+    /// accounts without one
+    #[arg(long)]
+    pub freeze_authority: Option<String>,
+    /// Create the mint under the Token-2022 program instead of SPL Token
+    #[arg(long)]
+    pub token_2022: bool,
+    /// Keypair file or signer URI of the mint address e.g one made with
+    /// `grind`, a new keypair is generated by default
+    #[arg(long)]
+    pub mint_keypair: Option<SignerSource>,
+    #[command(flatten)]
+    pub tx: TokenTxArgs,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct MintArgs {
+    /// The mint of the token to mint
+    #[arg(long)]
+    pub mint: String,
+    /// Amount of tokens to mint e.g 1.5
+    #[arg(long, allow_negative_numbers = true)]
+    pub amount: TokenAmount,
+    /// The wallet receiving the tokens in its associated token account,
+    /// defaults to the mint authority
+    #[arg(short, long)]
+    pub to: Option<String>,
+    /// Keypair file or signer URI of the mint authority, defaults to the
+    /// keypair in your config profile
+    #[arg(long)]
+    pub mint_authority: Option<SignerSource>,
+    #[command(flatten)]
+    pub tx: TokenTxArgs,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct BurnArgs {
+    /// The mint of the token to burn
+    #[arg(long)]
+    pub mint: String,
+    /// Amount of tokens to burn e.g 1.5 or ALL
+    #[arg(long, allow_negative_numbers = true)]
+    pub amount: TokenAmount,
+    /// Keypair file or signer URI of the wallet holding the tokens, defaults
+    /// to the keypair in your config profile
+    #[arg(long)]
+    pub owner: Option<SignerSource>,
+    #[command(flatten)]
+    pub tx: TokenTxArgs,
+}
+
+/// The mint authorities `set-authority` can change
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthorityKind {
+    /// Allowed to mint new tokens
+    Mint,
+    /// Allowed to freeze and thaw token accounts
+    Freeze,
+}
+
+impl AuthorityKind {
+    fn authority_type(&self) -> AuthorityType {
+        match self {
+            AuthorityKind::Mint => AuthorityType::MintTokens,
+            AuthorityKind::Freeze => AuthorityType::FreezeAccount,
+        }
+    }
+}
+
+impl fmt::Display for AuthorityKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthorityKind::Mint => write!(f, "mint"),
+            AuthorityKind::Freeze => write!(f, "freeze"),
+        }
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct SetAuthorityArgs {
+    /// The mint whose authority changes
+    #[arg(long)]
+    pub mint: String,
+    /// Which authority to change
+    #[arg(long, value_enum)]
+    pub authority_type: AuthorityKind,
+    /// Address of the new authority
+    #[arg(long, required_unless_present = "disable")]
+    pub new_authority: Option<String>,
+    /// Remove the authority for good e.g to fix the supply of the token
+    #[arg(long, conflicts_with = "new_authority")]
+    pub disable: bool,
+    /// Keypair file or signer URI of the current authority, defaults to the
+    /// keypair in your config profile
+    #[arg(long)]
+    pub authority: Option<SignerSource>,
+    #[command(flatten)]
+    pub tx: TokenTxArgs,
+}
+
+#[derive(Args, Clone, Debug)]
+#[command(group(ArgGroup::new("target").required(true).args(["owner", "account"])))]
+pub struct FreezeArgs {
+    /// The mint of the token account
+    #[arg(long)]
+    pub mint: String,
+    /// The wallet whose associated token account to act on
+    #[arg(long)]
+    pub owner: Option<String>,
+    /// The token account to act on, for accounts that are not associated
+    /// token accounts
+    #[arg(long)]
+    pub account: Option<String>,
+    /// Keypair file or signer URI of the freeze authority, defaults to the
+    /// keypair in your config profile
+    #[arg(long)]
+    pub freeze_authority: Option<SignerSource>,
+    #[command(flatten)]
+    pub tx: TokenTxArgs,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct TokenTransferArgs {
+    /// The mint of the token to transfer
+    #[arg(long)]
+    pub mint: String,
+    /// The keypair file or signer URI of the wallet holding the tokens,
+    /// defaults to the keypair in your config profile
+    #[arg(short, long)]
+    pub from: Option<SignerSource>,
+    /// The wallet address to transfer to, the tokens go to its associated
+    /// token account
+    #[arg(short, long)]
+    pub to: String,
+    /// Amount of tokens to transfer e.g 1.5 or ALL
+    #[arg(long, allow_negative_numbers = true)]
+    pub amount: TokenAmount,
+    /// Text attached to the transfer with the SPL Memo program e.g an
+    /// invoice number
+    #[arg(long, value_parser = parse_memo)]
+    pub memo: Option<String>,
+    /// Send on mainnet without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+    #[command(flatten)]
+    pub tx: TokenTxArgs,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TokenTransferCompleted {
+    pub from: String,
+    pub to: String,
+    pub mint: String,
+    pub source_account: String,
+    pub destination_account: String,
+    /// Raw amount in the mint's smallest unit
+    pub amount: u64,
+    pub ui_amount: String,
+    pub decimals: u8,
+    /// Whether the recipient's token account was created by this transfer
+    pub created_account: bool,
+    pub memo: Option<String>,
+    /// Total fee in lamports, including `priority_fee`
+    pub fee: u64,
+    pub priority_fee: u64,
+    pub signature: String,
+    pub slot: u64,
+    pub status: Stage,
+    /// How many times the transaction was re-signed after its blockhash expired
+    pub resigned: usize,
+}
+
+impl fmt::Display for TokenTransferCompleted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Transferred {} tokens of mint {}",
+            self.ui_amount.green().bold(),
+            self.mint.blue()
+        )?;
+        if self.created_account {
+            writeln!(
+                f,
+                "Created the recipient token account {}",
+                self.destination_account
+            )?;
+        }
+        if let Some(memo) = &self.memo {
+            writeln!(f, "Memo: {}", memo)?;
+        }
+        write_confirmation(
+            f,
+            self.fee,
+            self.priority_fee,
+            self.status,
+            self.slot,
+            self.resigned,
+        )?;
+        write!(
+            f,
+            "Transfer successful, signature: {}",
+            self.signature.yellow().bold()
+        )
+    }
+}
+
+impl CommandOutput for TokenTransferCompleted {}
+
+#[derive(Serialize, Debug)]
+pub struct MintCreated {
+    pub mint: String,
+    pub program: &'static str,
+    pub decimals: u8,
+    pub mint_authority: String,
+    pub freeze_authority: Option<String>,
+    /// Lamports deposited to make the mint account rent exempt
+    pub rent: u64,
+    /// Total fee in lamports, including `priority_fee`
+    pub fee: u64,
+    pub priority_fee: u64,
+    pub signature: String,
+    pub slot: u64,
+    pub status: Stage,
+    /// How many times the transaction was re-signed after its blockhash expired
+    pub resigned: usize,
+}
+
+impl fmt::Display for MintCreated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Created {} mint {} with {} decimals",
+            self.program,
+            self.mint.green().bold(),
+            self.decimals
+        )?;
+        writeln!(f, "Mint authority: {}", self.mint_authority)?;
+        match &self.freeze_authority {
+            Some(freeze_authority) => writeln!(f, "Freeze authority: {}", freeze_authority)?,
+            None => writeln!(f, "Freeze authority: {}", "none".dimmed())?,
+        }
+        writeln!(f, "Rent: {} SOL", format_sol(self.rent))?;
+        write_confirmation(
+            f,
+            self.fee,
+            self.priority_fee,
+            self.status,
+            self.slot,
+            self.resigned,
+        )?;
+        write!(f, "Signature: {}", self.signature.yellow().bold())
+    }
+}
+
+impl CommandOutput for MintCreated {}
+
+/// What a token management command did
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenOperation {
+    Mint,
+    Burn,
+    SetAuthority,
+    Freeze,
+    Thaw,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TokenOperationCompleted {
+    pub operation: TokenOperation,
+    pub mint: String,
+    /// The token account minted to, burned from, frozen or thawed
+    pub account: Option<String>,
+    /// Raw amount minted or burned, in the mint's smallest unit
+    pub amount: Option<u64>,
+    pub ui_amount: Option<String>,
+    /// Whether the recipient's token account was created to mint into it
+    pub created_account: bool,
+    /// The authority changed by `set-authority`
+    pub authority_type: Option<AuthorityKind>,
+    /// The new authority set by `set-authority`, empty once disabled
+    pub new_authority: Option<String>,
+    /// Total fee in lamports, including `priority_fee`
+    pub fee: u64,
+    pub priority_fee: u64,
+    pub signature: String,
+    pub slot: u64,
+    pub status: Stage,
+    /// How many times the transaction was re-signed after its blockhash expired
+    pub resigned: usize,
+}
+
+impl fmt::Display for TokenOperationCompleted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let account = self.account.as_deref().unwrap_or_default();
+        let ui_amount = self.ui_amount.as_deref().unwrap_or_default();
+        match self.operation {
+            TokenOperation::Mint => {
+                if self.created_account {
+                    writeln!(f, "Created the recipient token account {}", account)?;
+                }
+                writeln!(
+                    f,
+                    "Minted {} tokens of mint {} to {}",
+                    ui_amount.green().bold(),
+                    self.mint.blue(),
+                    account
+                )?
+            }
+            TokenOperation::Burn => writeln!(
+                f,
+                "Burned {} tokens of mint {} from {}",
+                ui_amount.green().bold(),
+                self.mint.blue(),
+                account
+            )?,
+            TokenOperation::SetAuthority => {
+                let kind = self
+                    .authority_type
+                    .map(|k| k.to_string())
+                    .unwrap_or_default();
+                match &self.new_authority {
+                    Some(new_authority) => writeln!(
+                        f,
+                        "Set the {} authority of mint {} to {}",
+                        kind,
+                        self.mint.blue(),
+                        new_authority.green().bold()
+                    )?,
+                    None => writeln!(
+                        f,
+                        "Disabled the {} authority of mint {}",
+                        kind,
+                        self.mint.blue()
+                    )?,
+                }
+            }
+            TokenOperation::Freeze => writeln!(f, "Froze token account {}", account.blue())?,
+            TokenOperation::Thaw => writeln!(f, "Thawed token account {}", account.blue())?,
+        }
+        write_confirmation(
+            f,
+            self.fee,
+            self.priority_fee,
+            self.status,
+            self.slot,
+            self.resigned,
+        )?;
+        write!(f, "Signature: {}", self.signature.yellow().bold())
+    }
+}
+
+impl CommandOutput for TokenOperationCompleted {}
+
+impl TokenOperationCompleted {
+    fn new(operation: TokenOperation, mint: &Pubkey, sent: SentTransaction) -> Self {
+        TokenOperationCompleted {
+            operation,
+            mint: mint.to_string(),
+            account: None,
+            amount: None,
+            ui_amount: None,
+            created_account: false,
+            authority_type: None,
+            new_authority: None,
+            fee: sent.fee,
+            priority_fee: sent.priority_fee,
+            signature: sent.confirmation.signature.to_string(),
+            slot: sent.confirmation.slot,
+            status: sent.confirmation.status,
+            resigned: sent.confirmation.resigned,
+        }
+    }
+}
+
+impl TokenArgs {
+    pub async fn token_handler(&self, settings: &Settings) -> Result<()> {
+        let rpc_client = get_rpc_client(settings)?;
+        match &self.command {
+            TokenCommands::Transfer(args) => args.transfer_tokens(&rpc_client, settings).await,
+            TokenCommands::CreateMint(args) => args.create_mint(&rpc_client, settings).await,
+            TokenCommands::Mint(args) => args.mint_tokens(&rpc_client, settings).await,
+            TokenCommands::Burn(args) => args.burn_tokens(&rpc_client, settings).await,
+            TokenCommands::SetAuthority(args) => args.set_authority(&rpc_client, settings).await,
+            TokenCommands::Freeze(args) => args.freeze(&rpc_client, settings, true).await,
+            TokenCommands::Thaw(args) => args.freeze(&rpc_client, settings, false).await,
+        }
+    }
+}
+
+impl TokenTransferArgs {
+    async fn transfer_tokens(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        let progress_bar = new_progress_bar(settings.output);
+
+        let owner = settings.keypair_or_default(&self.from)?.keypair()?;
+        let fee_payer = self.tx.fee_payer()?;
+        let payer: &Keypair = fee_payer.as_ref().unwrap_or(&owner);
+        let mint = parse_pubkey(&self.mint, "mint")?;
+        let recipient = parse_pubkey(&self.to, "recipient")?;
+
+        progress_bar.set_message("Looking up token accounts...");
+        let mint_info = get_mint(rpc_client, &mint).await?;
+        let source = get_associated_token_address_with_program_id(
+            &owner.pubkey(),
+            &mint,
+            &mint_info.program_id,
+        );
+        let destination =
+            get_associated_token_address_with_program_id(&recipient, &mint, &mint_info.program_id);
+        let accounts = rpc_client
+            .get_multiple_accounts(&[source, destination])
+            .await?;
+        let balance = match &accounts[0] {
+            Some(account) => token_account_amount(&source, &account.data)?,
+            None => anyhow::bail!("{} has no token account for mint {}", owner.pubkey(), mint),
+        };
+        let amount = self.amount.base_units(mint_info.decimals, balance)?;
+        if amount == 0 {
+            anyhow::bail!("Nothing to transfer, the amount is 0");
+        }
+        if amount > balance {
+            anyhow::bail!(
+                "Insufficient token balance, {} available",
+                format_token_amount(balance, mint_info.decimals)
+            );
+        }
+
+        progress_bar.set_message("Creating transaction...");
+        let created_account = accounts[1].is_none();
+        let mut instructions: Vec<Instruction> = Vec::new();
+        if created_account {
+            instructions.push(create_associated_token_account_idempotent(
+                &payer.pubkey(),
+                &recipient,
+                &mint,
+                &mint_info.program_id,
+            ));
+        }
+        instructions.push(spl_token_2022::instruction::transfer_checked(
+            &mint_info.program_id,
+            &source,
+            &mint,
+            &destination,
+            &owner.pubkey(),
+            &[],
+            amount,
+            mint_info.decimals,
+        )?);
+        if let Some(memo) = &self.memo {
+            instructions.push(memo_instruction(memo, &owner.pubkey()));
+        }
+        let priced = price_token_transaction(
+            rpc_client,
+            &self.tx.priority_fee,
+            &instructions,
+            &payer.pubkey(),
+        )
+        .await?;
+
+        let guard = MainnetGuard::new(rpc_client, settings).await?;
+        guard.check_allowlist(&recipient)?;
+        let mut summary = vec![
+            format!("From: {}", owner.pubkey()),
+            format!("To: {}", recipient),
+            format!(
+                "Amount: {} tokens of mint {}",
+                format_token_amount(amount, mint_info.decimals),
+                mint
+            ),
+        ];
+        if created_account {
+            summary.push(format!("Creates the token account {}", destination));
+        }
+        summary.push(format!("Fee: {} SOL", format_sol(priced.fee)));
+        progress_bar.suspend(|| guard.confirm(&summary, self.yes))?;
+
+        let sent =
+            send_priced_token_transaction(rpc_client, priced, payer, &[&owner], &progress_bar)
+                .await?;
+        let output = TokenTransferCompleted {
+            from: owner.pubkey().to_string(),
+            to: recipient.to_string(),
+            mint: mint.to_string(),
+            source_account: source.to_string(),
+            destination_account: destination.to_string(),
+            amount,
+            ui_amount: format_token_amount(amount, mint_info.decimals),
+            decimals: mint_info.decimals,
+            created_account,
+            memo: self.memo.clone(),
+            fee: sent.fee,
+            priority_fee: sent.priority_fee,
+            signature: sent.confirmation.signature.to_string(),
+            slot: sent.confirmation.slot,
+            status: sent.confirmation.status,
+            resigned: sent.confirmation.resigned,
+        };
+        print_output(&output, settings.output)?;
+        Ok(())
+    }
+}
+
+impl CreateMintArgs {
+    async fn create_mint(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        let progress_bar = new_progress_bar(settings.output);
+
+        let payer = settings.keypair_or_default(&self.tx.fee_payer)?.keypair()?;
+        let mint = match &self.mint_keypair {
+            Some(source) => source.keypair()?,
+            None => Keypair::new(),
+        };
+        let mint_authority = match &self.mint_authority {
+            Some(address) => parse_pubkey(address, "mint authority")?,
+            None => payer.pubkey(),
+        };
+        let freeze_authority = self
+            .freeze_authority
+            .as_deref()
+            .map(|address| parse_pubkey(address, "freeze authority"))
+            .transpose()?;
+        let program_id = if self.token_2022 {
+            spl_token_2022::id()
+        } else {
+            spl_token::id()
+        };
+
+        progress_bar.set_message("Creating transaction...");
+        if rpc_client
+            .get_account_with_commitment(&mint.pubkey(), rpc_client.commitment())
+            .await?
+            .value
+            .is_some()
+        {
+            anyhow::bail!("Account {} already exists", mint.pubkey());
+        }
+        let rent = rpc_client
+            .get_minimum_balance_for_rent_exemption(Mint::LEN)
+            .await?;
+        let instructions = vec![
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &mint.pubkey(),
+                rent,
+                Mint::LEN as u64,
+                &program_id,
+            ),
+            spl_token_2022::instruction::initialize_mint2(
+                &program_id,
+                &mint.pubkey(),
+                &mint_authority,
+                freeze_authority.as_ref(),
+                self.decimals,
+            )?,
+        ];
+        let sent = send_token_transaction(
+            rpc_client,
+            &self.tx.priority_fee,
+            &instructions,
+            &payer,
+            &[&mint],
+            &progress_bar,
+        )
+        .await?;
+        let output = MintCreated {
+            mint: mint.pubkey().to_string(),
+            program: program_name(&program_id),
+            decimals: self.decimals,
+            mint_authority: mint_authority.to_string(),
+            freeze_authority: freeze_authority.map(|a| a.to_string()),
+            rent,
+            fee: sent.fee,
+            priority_fee: sent.priority_fee,
+            signature: sent.confirmation.signature.to_string(),
+            slot: sent.confirmation.slot,
+            status: sent.confirmation.status,
+            resigned: sent.confirmation.resigned,
+        };
+        print_output(&output, settings.output)?;
+        Ok(())
+    }
+}
+
+impl MintArgs {
+    async fn mint_tokens(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        let progress_bar = new_progress_bar(settings.output);
+
+        let authority = settings
+            .keypair_or_default(&self.mint_authority)?
+            .keypair()?;
+        let fee_payer = self.tx.fee_payer()?;
+        let payer: &Keypair = fee_payer.as_ref().unwrap_or(&authority);
+        let mint = parse_pubkey(&self.mint, "mint")?;
+        let recipient = match &self.to {
+            Some(address) => parse_pubkey(address, "recipient")?,
+            None => authority.pubkey(),
+        };
+
+        progress_bar.set_message("Looking up token accounts...");
+        let mint_info = get_mint(rpc_client, &mint).await?;
+        check_authority(
+            mint_info.mint_authority,
+            &authority.pubkey(),
+            &mint,
+            AuthorityKind::Mint,
+        )?;
+        let TokenAmount::Decimal(_) = self.amount else {
+            anyhow::bail!("`ALL` is not an amount that can be minted");
+        };
+        let amount = self.amount.base_units(mint_info.decimals, 0)?;
+        if amount == 0 {
+            anyhow::bail!("Nothing to mint, the amount is 0");
+        }
+        let destination =
+            get_associated_token_address_with_program_id(&recipient, &mint, &mint_info.program_id);
+        let created_account = rpc_client
+            .get_account_with_commitment(&destination, rpc_client.commitment())
+            .await?
+            .value
+            .is_none();
+
+        progress_bar.set_message("Creating transaction...");
+        let mut instructions: Vec<Instruction> = Vec::new();
+        if created_account {
+            instructions.push(create_associated_token_account_idempotent(
+                &payer.pubkey(),
+                &recipient,
+                &mint,
+                &mint_info.program_id,
+            ));
+        }
+        instructions.push(spl_token_2022::instruction::mint_to_checked(
+            &mint_info.program_id,
+            &mint,
+            &destination,
+            &authority.pubkey(),
+            &[],
+            amount,
+            mint_info.decimals,
+        )?);
+        let sent = send_token_transaction(
+            rpc_client,
+            &self.tx.priority_fee,
+            &instructions,
+            payer,
+            &[&authority],
+            &progress_bar,
+        )
+        .await?;
+        let output = TokenOperationCompleted {
+            account: Some(destination.to_string()),
+            amount: Some(amount),
+            ui_amount: Some(format_token_amount(amount, mint_info.decimals)),
+            created_account,
+            ..TokenOperationCompleted::new(TokenOperation::Mint, &mint, sent)
+        };
+        print_output(&output, settings.output)?;
+        Ok(())
+    }
+}
+
+impl BurnArgs {
+    async fn burn_tokens(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        let progress_bar = new_progress_bar(settings.output);
+
+        let owner = settings.keypair_or_default(&self.owner)?.keypair()?;
+        let fee_payer = self.tx.fee_payer()?;
+        let payer: &Keypair = fee_payer.as_ref().unwrap_or(&owner);
+        let mint = parse_pubkey(&self.mint, "mint")?;
+
+        progress_bar.set_message("Looking up token accounts...");
+        let mint_info = get_mint(rpc_client, &mint).await?;
+        let source = get_associated_token_address_with_program_id(
+            &owner.pubkey(),
+            &mint,
+            &mint_info.program_id,
+        );
+        let balance = match rpc_client
+            .get_account_with_commitment(&source, rpc_client.commitment())
+            .await?
+            .value
+        {
+            Some(account) => token_account_amount(&source, &account.data)?,
+            None => anyhow::bail!("{} has no token account for mint {}", owner.pubkey(), mint),
+        };
+        let amount = self.amount.base_units(mint_info.decimals, balance)?;
+        if amount == 0 {
+            anyhow::bail!("Nothing to burn, the amount is 0");
+        }
+        if amount > balance {
+            anyhow::bail!(
+                "Insufficient token balance, {} available",
+                format_token_amount(balance, mint_info.decimals)
+            );
+        }
+
+        progress_bar.set_message("Creating transaction...");
+        let instructions = vec![spl_token_2022::instruction::burn_checked(
+            &mint_info.program_id,
+            &source,
+            &mint,
+            &owner.pubkey(),
+            &[],
+            amount,
+            mint_info.decimals,
+        )?];
+        let sent = send_token_transaction(
+            rpc_client,
+            &self.tx.priority_fee,
+            &instructions,
+            payer,
+            &[&owner],
+            &progress_bar,
+        )
+        .await?;
+        let output = TokenOperationCompleted {
+            account: Some(source.to_string()),
+            amount: Some(amount),
+            ui_amount: Some(format_token_amount(amount, mint_info.decimals)),
+            ..TokenOperationCompleted::new(TokenOperation::Burn, &mint, sent)
+        };
+        print_output(&output, settings.output)?;
+        Ok(())
+    }
+}
+
+impl SetAuthorityArgs {
+    async fn set_authority(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        let progress_bar = new_progress_bar(settings.output);
+
+        let authority = settings.keypair_or_default(&self.authority)?.keypair()?;
+        let fee_payer = self.tx.fee_payer()?;
+        let payer: &Keypair = fee_payer.as_ref().unwrap_or(&authority);
+        let mint = parse_pubkey(&self.mint, "mint")?;
+        let new_authority = self
+            .new_authority
+            .as_deref()
+            .map(|address| parse_pubkey(address, "new authority"))
+            .transpose()?;
+
+        progress_bar.set_message("Looking up mint...");
+        let mint_info = get_mint(rpc_client, &mint).await?;
+        let current = match self.authority_type {
+            AuthorityKind::Mint => mint_info.mint_authority,
+            AuthorityKind::Freeze => mint_info.freeze_authority,
+        };
+        check_authority(current, &authority.pubkey(), &mint, self.authority_type)?;
+
+        progress_bar.set_message("Creating transaction...");
+        let instructions = vec![spl_token_2022::instruction::set_authority(
+            &mint_info.program_id,
+            &mint,
+            new_authority.as_ref(),
+            self.authority_type.authority_type(),
+            &authority.pubkey(),
+            &[],
+        )?];
+        let sent = send_token_transaction(
+            rpc_client,
+            &self.tx.priority_fee,
+            &instructions,
+            payer,
+            &[&authority],
+            &progress_bar,
+        )
+        .await?;
+        let output = TokenOperationCompleted {
+            authority_type: Some(self.authority_type),
+            new_authority: new_authority.map(|a| a.to_string()),
+            ..TokenOperationCompleted::new(TokenOperation::SetAuthority, &mint, sent)
+        };
+        print_output(&output, settings.output)?;
+        Ok(())
+    }
+}
+
+impl FreezeArgs {
+    /// Freezes the token account, or thaws it when `freeze` is false
+    async fn freeze(
+        &self,
+        rpc_client: &RpcClient,
+        settings: &Settings,
+        freeze: bool,
+    ) -> Result<()> {
+        let progress_bar = new_progress_bar(settings.output);
+
+        let authority = settings
+            .keypair_or_default(&self.freeze_authority)?
+            .keypair()?;
+        let fee_payer = self.tx.fee_payer()?;
+        let payer: &Keypair = fee_payer.as_ref().unwrap_or(&authority);
+        let mint = parse_pubkey(&self.mint, "mint")?;
+
+        progress_bar.set_message("Looking up token account...");
+        let mint_info = get_mint(rpc_client, &mint).await?;
+        check_authority(
+            mint_info.freeze_authority,
+            &authority.pubkey(),
+            &mint,
+            AuthorityKind::Freeze,
+        )?;
+        let account = match (&self.account, &self.owner) {
+            (Some(address), _) => parse_pubkey(address, "token account")?,
+            (None, Some(owner)) => get_associated_token_address_with_program_id(
+                &parse_pubkey(owner, "owner")?,
+                &mint,
+                &mint_info.program_id,
+            ),
+            (None, None) => unreachable!("clap requires --owner or --account"),
+        };
+        let state = match rpc_client
+            .get_account_with_commitment(&account, rpc_client.commitment())
+            .await?
+            .value
+        {
+            Some(data) => unpack_token_account(&account, &data.data)?,
+            None => anyhow::bail!("Token account {} does not exist", account),
+        };
+        if state.mint != mint {
+            anyhow::bail!(
+                "{} is a token account of mint {}, not {}",
+                account,
+                state.mint,
+                mint
+            );
+        }
+        if freeze && state.is_frozen() {
+            anyhow::bail!("Token account {} is already frozen", account);
+        }
+        if !freeze && !state.is_frozen() {
+            anyhow::bail!("Token account {} is not frozen", account);
+        }
+
+        progress_bar.set_message("Creating transaction...");
+        let instruction = if freeze {
+            spl_token_2022::instruction::freeze_account(
+                &mint_info.program_id,
+                &account,
+                &mint,
+                &authority.pubkey(),
+                &[],
+            )?
+        } else {
+            spl_token_2022::instruction::thaw_account(
+                &mint_info.program_id,
+                &account,
+                &mint,
+                &authority.pubkey(),
+                &[],
+            )?
+        };
+        let sent = send_token_transaction(
+            rpc_client,
+            &self.tx.priority_fee,
+            &[instruction],
+            payer,
+            &[&authority],
+            &progress_bar,
+        )
+        .await?;
+        let operation = if freeze {
+            TokenOperation::Freeze
+        } else {
+            TokenOperation::Thaw
+        };
+        let output = TokenOperationCompleted {
+            account: Some(account.to_string()),
+            ..TokenOperationCompleted::new(operation, &mint, sent)
+        };
+        print_output(&output, settings.output)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,978 @@
+use crate::amount::format_sol;
+use crate::args::get_rpc_client;
+use crate::config::Settings;
+use crate::output::{print_output, CommandOutput};
+use crate::sender::new_progress_bar;
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use clap::{Args, Subcommand};
+use colored::*;
+use serde::Serialize;
+use serde_json::Value;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_client::rpc_request::RpcRequest;
+use solana_program::borsh1::try_from_slice_unchecked;
+use solana_program::decode_error::DecodeError;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use solana_sdk::compute_budget::{self, ComputeBudgetInstruction};
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::stake::instruction::StakeError;
+use solana_sdk::system_instruction::SystemError;
+use solana_sdk::transaction::TransactionError;
+use solana_sdk::{stake, system_program};
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::parse_instruction::ParsedInstruction;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiInstruction, UiMessage,
+    UiParsedInstruction, UiParsedMessage, UiTransactionEncoding, UiTransactionStatusMeta,
+    UiTransactionTokenBalance,
+};
+use spl_associated_token_account::error::AssociatedTokenAccountError;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Args, Clone, Debug)]
+pub struct TxArgs {
+    #[command(subcommand)]
+    pub command: TxCommands,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum TxCommands {
+    /// Fetch a transaction and decode its instructions, balance changes and
+    /// logs
+    Show(TxShowArgs),
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct TxShowArgs {
+    /// The signature of the transaction
+    pub signature: Signature,
+}
+
+/// The commitment to read past transactions at, the RPC node only serves them
+/// once confirmed
+pub fn history_commitment(rpc_client: &RpcClient) -> CommitmentConfig {
+    match rpc_client.commitment().commitment {
+        CommitmentLevel::Processed => CommitmentConfig::confirmed(),
+        _ => rpc_client.commitment(),
+    }
+}
+
+/// Fetches a transaction with its instructions parsed by the RPC node
+pub async fn fetch_transaction(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::JsonParsed),
+        commitment: Some(history_commitment(rpc_client)),
+        max_supported_transaction_version: Some(0),
+    };
+    // The node answers null for signatures it does not know, which
+    // `get_transaction_with_config` reports as a parse error
+    let transaction: Option<EncodedConfirmedTransactionWithStatusMeta> = rpc_client
+        .send(
+            RpcRequest::GetTransaction,
+            serde_json::json!([signature.to_string(), config]),
+        )
+        .await
+        .with_context(|| format!("Failed to fetch transaction {}", signature))?;
+    transaction.with_context(|| {
+        format!(
+            "Transaction {} not found, it may not be confirmed yet or the RPC node no longer keeps it",
+            signature
+        )
+    })
+}
+
+/// A fetched transaction's parsed message and status
+pub struct ParsedTransaction<'a> {
+    pub message: &'a UiParsedMessage,
+    pub meta: &'a UiTransactionStatusMeta,
+}
+
+impl<'a> ParsedTransaction<'a> {
+    pub fn new(transaction: &'a EncodedConfirmedTransactionWithStatusMeta) -> Result<Self> {
+        let EncodedTransaction::Json(ui_transaction) = &transaction.transaction.transaction else {
+            anyhow::bail!("The RPC node did not return a parsed transaction");
+        };
+        let UiMessage::Parsed(message) = &ui_transaction.message else {
+            anyhow::bail!("The RPC node did not return a parsed transaction");
+        };
+        let meta = transaction
+            .transaction
+            .meta
+            .as_ref()
+            .context("The RPC node returned a transaction without its status")?;
+        Ok(ParsedTransaction { message, meta })
+    }
+
+    /// Lamports `address` gained, or lost when negative, fee included
+    pub fn lamports_delta(&self, address: &str) -> i64 {
+        self.message
+            .account_keys
+            .iter()
+            .position(|key| key.pubkey == address)
+            .and_then(|i| {
+                Some((
+                    *self.meta.pre_balances.get(i)?,
+                    *self.meta.post_balances.get(i)?,
+                ))
+            })
+            .map_or(0, |(pre, post)| signed_delta(pre, post))
+    }
+
+    /// The wallet owning each token account whose balance the transaction
+    /// touched
+    fn token_account_owners(&self) -> HashMap<&str, &str> {
+        let mut owners = HashMap::new();
+        for balances in [
+            &self.meta.pre_token_balances,
+            &self.meta.post_token_balances,
+        ] {
+            let OptionSerializer::Some(balances) = balances else {
+                continue;
+            };
+            for balance in balances {
+                let account = self
+                    .message
+                    .account_keys
+                    .get(balance.account_index as usize);
+                if let (Some(account), OptionSerializer::Some(owner)) = (account, &balance.owner) {
+                    owners.insert(account.pubkey.as_str(), owner.as_str());
+                }
+            }
+        }
+        owners
+    }
+
+    /// The other side of the first SOL or token transfer `address` takes part
+    /// in, falling back to the account whose balance moved the most the other
+    /// way
+    pub fn counterparty(&self, address: &str) -> Option<String> {
+        let owners = self.token_account_owners();
+        let owner_of = |account: &str| owners.get(account).copied().unwrap_or(account).to_string();
+        for instruction in self.parsed_instructions() {
+            let info = &instruction.parsed["info"];
+            let account = |key: &str| info[key].as_str().map(str::to_string);
+            let (source, destination) = match (
+                instruction.program.as_str(),
+                instruction.parsed["type"].as_str().unwrap_or_default(),
+            ) {
+                ("system", "transfer" | "transferWithSeed") => {
+                    (account("source"), account("destination"))
+                }
+                ("system", "createAccount" | "createAccountWithSeed") => {
+                    (account("source"), account("newAccount"))
+                }
+                // Token accounts stand for the wallets owning them
+                ("spl-token" | "spl-token-2022", "transfer" | "transferChecked") => (
+                    account("authority")
+                        .or_else(|| account("multisigAuthority"))
+                        .or_else(|| info["source"].as_str().map(owner_of)),
+                    info["destination"].as_str().map(owner_of),
+                ),
+                _ => continue,
+            };
+            match (source, destination) {
+                (Some(source), Some(destination)) if source == address => return Some(destination),
+                (Some(source), Some(destination)) if destination == address => return Some(source),
+                _ => {}
+            }
+        }
+
+        let delta = self.lamports_delta(address);
+        self.message
+            .account_keys
+            .iter()
+            .map(|key| (key.pubkey.as_str(), self.lamports_delta(&key.pubkey)))
+            .filter(|(key, other)| *key != address && other.signum() == -delta.signum())
+            .max_by_key(|(_, other)| other.abs())
+            .filter(|(_, other)| *other != 0)
+            .map(|(key, _)| key.to_string())
+    }
+
+    /// Explains why the transaction failed in plain words, naming the program
+    /// behind an instruction error and decoding its custom error code
+    pub fn explain_error(&self, error: &TransactionError) -> String {
+        let account = |index: u8| {
+            self.message
+                .account_keys
+                .get(index as usize)
+                .map_or_else(|| format!("#{}", index), |key| key.pubkey.clone())
+        };
+        match error {
+            TransactionError::InstructionError(index, error) => {
+                let index = *index as usize;
+                let (program, program_id) = self
+                    .message
+                    .instructions
+                    .get(index)
+                    .map(|instruction| decode_instruction(instruction, self.message))
+                    .map_or_else(Default::default, |instruction| {
+                        (instruction.program, instruction.program_id)
+                    });
+                let reason = match error {
+                    InstructionError::Custom(code) => match custom_error(&program_id, *code) {
+                        Some(reason) => format!("{} (custom error {})", reason, code),
+                        None => format!("custom program error {:#x}", code),
+                    },
+                    error => error.to_string(),
+                };
+                format!(
+                    "Instruction #{} ({}) failed: {}",
+                    index + 1,
+                    program,
+                    reason
+                )
+            }
+            TransactionError::InsufficientFundsForRent { account_index } => format!(
+                "{} would be left below the rent-exempt minimum",
+                account(*account_index)
+            ),
+            TransactionError::InsufficientFundsForFee => {
+                format!("The fee payer {} can not pay the fee", account(0))
+            }
+            TransactionError::AccountNotFound => {
+                format!("The fee payer {} has no account, fund it first", account(0))
+            }
+            TransactionError::BlockhashNotFound => {
+                "The recent blockhash expired before the transaction landed".to_string()
+            }
+            error => error.to_string(),
+        }
+    }
+
+    /// The top level instructions the RPC node could parse
+    fn parsed_instructions(&self) -> impl Iterator<Item = &ParsedInstruction> {
+        self.message
+            .instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => Some(parsed),
+                _ => None,
+            })
+    }
+
+    /// One short name per top level instruction e.g `system: transfer`,
+    /// leaving out compute budget instructions
+    pub fn instruction_summary(&self) -> Vec<String> {
+        self.message
+            .instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => {
+                    Some(match parsed.parsed["type"].as_str() {
+                        Some(kind) => format!("{}: {}", parsed.program, kind),
+                        None => parsed.program.clone(),
+                    })
+                }
+                UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(decoded))
+                    if decoded.program_id != compute_budget::id().to_string() =>
+                {
+                    Some(decoded.program_id.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The text of every SPL Memo instruction
+    pub fn memos(&self) -> Vec<String> {
+        self.parsed_instructions()
+            .filter(|instruction| instruction.program == "spl-memo")
+            .filter_map(|instruction| match &instruction.parsed {
+                Value::String(memo) => Some(memo.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Translates the custom error `code` of the programs sol-dash knows about
+fn custom_error(program_id: &str, code: u32) -> Option<String> {
+    let program_id = Pubkey::from_str(program_id).ok()?;
+    if program_id == system_program::id() {
+        let error: Option<SystemError> = SystemError::decode_custom_error_to_enum(code);
+        error.map(|error| error.to_string())
+    } else if program_id == spl_token::id() {
+        let error: Option<spl_token::error::TokenError> =
+            spl_token::error::TokenError::decode_custom_error_to_enum(code);
+        error.map(|error| error.to_string())
+    } else if program_id == spl_token_2022::id() {
+        let error: Option<spl_token_2022::error::TokenError> =
+            spl_token_2022::error::TokenError::decode_custom_error_to_enum(code);
+        error.map(|error| error.to_string())
+    } else if program_id == spl_associated_token_account::id() {
+        let error: Option<AssociatedTokenAccountError> =
+            AssociatedTokenAccountError::decode_custom_error_to_enum(code);
+        error.map(|error| error.to_string())
+    } else if program_id == stake::program::id() {
+        let error: Option<StakeError> = StakeError::decode_custom_error_to_enum(code);
+        error.map(|error| error.to_string())
+    } else {
+        None
+    }
+}
+
+/// Decodes a compute budget instruction from its base58 data, the RPC node
+/// leaves these undecoded
+fn decode_compute_budget(data: &str) -> Option<(String, Value)> {
+    let data = bs58::decode(data).into_vec().ok()?;
+    let instruction = try_from_slice_unchecked::<ComputeBudgetInstruction>(&data).ok()?;
+    let (kind, info) = match instruction {
+        ComputeBudgetInstruction::Unused => ("unused", Value::Null),
+        ComputeBudgetInstruction::RequestHeapFrame(bytes) => {
+            ("requestHeapFrame", serde_json::json!({ "bytes": bytes }))
+        }
+        ComputeBudgetInstruction::SetComputeUnitLimit(units) => {
+            ("setComputeUnitLimit", serde_json::json!({ "units": units }))
+        }
+        ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports) => (
+            "setComputeUnitPrice",
+            serde_json::json!({ "microLamports": micro_lamports }),
+        ),
+        ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit(bytes) => (
+            "setLoadedAccountsDataSizeLimit",
+            serde_json::json!({ "bytes": bytes }),
+        ),
+    };
+    Some((kind.to_string(), info))
+}
+
+#[derive(Serialize, Debug)]
+pub struct DecodedInstruction {
+    /// Name of the program e.g `system`, or its id when sol-dash does not
+    /// know it
+    pub program: String,
+    pub program_id: String,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    /// Decoded arguments and accounts, or the text of a memo
+    pub info: Option<Value>,
+    /// Accounts of an instruction that could not be decoded
+    pub accounts: Vec<String>,
+    /// Base58 data of an instruction that could not be decoded
+    pub data: Option<String>,
+    /// Depth of a cross-program invocation, 1 for top level instructions
+    pub stack_height: Option<u32>,
+    pub inner_instructions: Vec<DecodedInstruction>,
+}
+
+fn decode_instruction(
+    instruction: &UiInstruction,
+    message: &UiParsedMessage,
+) -> DecodedInstruction {
+    let mut decoded = DecodedInstruction {
+        program: String::new(),
+        program_id: String::new(),
+        kind: None,
+        info: None,
+        accounts: Vec::new(),
+        data: None,
+        stack_height: None,
+        inner_instructions: Vec::new(),
+    };
+    match instruction {
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => {
+            decoded.program = parsed.program.clone();
+            decoded.program_id = parsed.program_id.clone();
+            decoded.stack_height = parsed.stack_height;
+            match &parsed.parsed {
+                Value::Object(object) => {
+                    decoded.kind = object
+                        .get("type")
+                        .and_then(Value::as_str)
+                        .map(str::to_string);
+                    decoded.info = object.get("info").cloned();
+                }
+                // Memos are parsed to their bare text
+                other => decoded.info = Some(other.clone()),
+            }
+        }
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(partial)) => {
+            decoded.program_id = partial.program_id.clone();
+            decoded.stack_height = partial.stack_height;
+            match decode_compute_budget(&partial.data)
+                .filter(|_| partial.program_id == compute_budget::id().to_string())
+            {
+                Some((kind, info)) => {
+                    decoded.program = "compute-budget".to_string();
+                    decoded.kind = Some(kind);
+                    decoded.info = Some(info).filter(|info| !info.is_null());
+                }
+                None => {
+                    decoded.program = partial.program_id.clone();
+                    decoded.accounts = partial.accounts.clone();
+                    decoded.data = Some(partial.data.clone());
+                }
+            }
+        }
+        UiInstruction::Compiled(compiled) => {
+            let account = |index: u8| {
+                message
+                    .account_keys
+                    .get(index as usize)
+                    .map(|key| key.pubkey.clone())
+                    .unwrap_or_default()
+            };
+            decoded.program_id = account(compiled.program_id_index);
+            decoded.program = decoded.program_id.clone();
+            decoded.accounts = compiled
+                .accounts
+                .iter()
+                .map(|&index| account(index))
+                .collect();
+            decoded.data = Some(compiled.data.clone());
+            decoded.stack_height = compiled.stack_height;
+        }
+    }
+    decoded
+}
+
+/// The SOL balance of an account before and after the transaction
+#[derive(Serialize, Debug)]
+pub struct AccountBalance {
+    pub address: String,
+    pub signer: bool,
+    pub writable: bool,
+    /// Lamports before the transaction
+    pub pre: u64,
+    /// Lamports after the transaction
+    pub post: u64,
+}
+
+/// The token balance of a token account before and after the transaction,
+/// as UI amounts
+#[derive(Serialize, Debug)]
+pub struct TokenBalanceChange {
+    pub account: String,
+    pub owner: Option<String>,
+    pub mint: String,
+    pub decimals: u8,
+    /// Unset when the transaction created the token account
+    pub pre: Option<String>,
+    /// Unset when the transaction closed the token account
+    pub post: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TransactionDetails {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub time: Option<String>,
+    pub success: bool,
+    /// Why the transaction failed, in plain words
+    pub error: Option<String>,
+    /// The error as reported by the RPC node
+    pub raw_error: Option<TransactionError>,
+    /// Fee in lamports, including the priority fee
+    pub fee: u64,
+    pub fee_payer: String,
+    pub signers: Vec<String>,
+    pub recent_blockhash: String,
+    pub compute_units_consumed: Option<u64>,
+    pub instructions: Vec<DecodedInstruction>,
+    pub balances: Vec<AccountBalance>,
+    pub token_balances: Vec<TokenBalanceChange>,
+    pub logs: Vec<String>,
+}
+
+/// Formats the decoded arguments of an instruction as a single value
+fn format_info_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Object(object) if object.contains_key("uiAmountString") => object["uiAmountString"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn write_instruction(
+    f: &mut fmt::Formatter<'_>,
+    number: &str,
+    instruction: &DecodedInstruction,
+    indent: usize,
+) -> fmt::Result {
+    let pad = " ".repeat(indent);
+    write!(f, "\n{}#{} {}", pad, number, instruction.program.bold())?;
+    if let Some(kind) = &instruction.kind {
+        write!(f, ": {}", kind)?;
+    }
+    match &instruction.info {
+        Some(Value::Object(info)) => {
+            for (key, value) in info {
+                write!(f, "\n{}    {}: {}", pad, key, format_info_value(value))?;
+                if key == "lamports" {
+                    if let Some(lamports) = value.as_u64() {
+                        write!(f, " ({} SOL)", format_sol(lamports))?;
+                    }
+                }
+            }
+        }
+        Some(info) => write!(f, "\n{}    {}", pad, format_info_value(info))?,
+        None => {}
+    }
+    for (i, account) in instruction.accounts.iter().enumerate() {
+        write!(f, "\n{}    account {}: {}", pad, i, account)?;
+    }
+    if let Some(data) = &instruction.data {
+        write!(f, "\n{}    data: {}", pad, data)?;
+    }
+    for (i, inner) in instruction.inner_instructions.iter().enumerate() {
+        // Nested invocations start at stack height 2
+        let depth = inner.stack_height.unwrap_or(2).saturating_sub(1) as usize;
+        write_instruction(
+            f,
+            &format!("{}.{}", number, i + 1),
+            inner,
+            indent + 2 * depth,
+        )?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for TransactionDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Signature: {}", self.signature.yellow())?;
+        if self.success {
+            write!(f, "\nStatus: {}", "success".green())?;
+        } else {
+            write!(f, "\nStatus: {}", "failed".red().bold())?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, "\nError: {}", error.red())?;
+        }
+        write!(f, "\nSlot: {}", self.slot)?;
+        if let Some(time) = &self.time {
+            write!(f, "\nTime: {}", time)?;
+        }
+        write!(f, "\nFee: {} SOL", format_sol(self.fee))?;
+        write!(f, "\nFee payer: {}", self.fee_payer.blue())?;
+        write!(f, "\nSigners: {}", self.signers.join(", "))?;
+        write!(f, "\nRecent blockhash: {}", self.recent_blockhash)?;
+        if let Some(units) = self.compute_units_consumed {
+            write!(f, "\nCompute units consumed: {}", units)?;
+        }
+
+        write!(f, "\n\n{}", "Instructions:".bold())?;
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            write_instruction(f, &(i + 1).to_string(), instruction, 2)?;
+        }
+
+        write!(f, "\n\n{}", "SOL balances:".bold())?;
+        for balance in &self.balances {
+            write!(
+                f,
+                "\n  {} {} -> {} SOL",
+                balance.address,
+                format_sol(balance.pre),
+                format_sol(balance.post)
+            )?;
+            if balance.post != balance.pre {
+                write!(
+                    f,
+                    " ({})",
+                    format_delta(signed_delta(balance.pre, balance.post))
+                )?;
+            }
+        }
+
+        if !self.token_balances.is_empty() {
+            write!(f, "\n\n{}", "Token balances:".bold())?;
+            for balance in &self.token_balances {
+                write!(
+                    f,
+                    "\n  {} {} -> {}",
+                    balance.account,
+                    balance.pre.as_deref().unwrap_or("none"),
+                    balance.post.as_deref().unwrap_or("closed"),
+                )?;
+                write!(f, "\n    mint: {}", balance.mint)?;
+                if let Some(owner) = &balance.owner {
+                    write!(f, "\n    owner: {}", owner)?;
+                }
+            }
+        }
+
+        if !self.logs.is_empty() {
+            write!(f, "\n\n{}", "Logs:".bold())?;
+            for log in &self.logs {
+                write!(f, "\n  {}", log)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `TransactionDetails` as a single CSV record, with the instructions
+/// summarized and lists joined by `; `
+#[derive(Serialize)]
+struct TransactionRow<'a> {
+    signature: &'a str,
+    slot: u64,
+    block_time: Option<i64>,
+    time: Option<&'a str>,
+    success: bool,
+    error: Option<&'a str>,
+    fee: u64,
+    fee_payer: &'a str,
+    signers: String,
+    recent_blockhash: &'a str,
+    compute_units_consumed: Option<u64>,
+    instructions: String,
+    logs: String,
+}
+
+impl CommandOutput for TransactionDetails {
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        let instructions = self
+            .instructions
+            .iter()
+            .map(|instruction| match &instruction.kind {
+                Some(kind) => format!("{}: {}", instruction.program, kind),
+                None => instruction.program.clone(),
+            })
+            .collect::<Vec<_>>();
+        writer.serialize(TransactionRow {
+            signature: &self.signature,
+            slot: self.slot,
+            block_time: self.block_time,
+            time: self.time.as_deref(),
+            success: self.success,
+            error: self.error.as_deref(),
+            fee: self.fee,
+            fee_payer: &self.fee_payer,
+            signers: self.signers.join("; "),
+            recent_blockhash: &self.recent_blockhash,
+            compute_units_consumed: self.compute_units_consumed,
+            instructions: instructions.join("; "),
+            logs: self.logs.join("; "),
+        })?;
+        Ok(())
+    }
+}
+
+/// Pairs up the token balances before and after the transaction by account
+fn token_balance_changes(
+    message: &UiParsedMessage,
+    pre: &OptionSerializer<Vec<UiTransactionTokenBalance>>,
+    post: &OptionSerializer<Vec<UiTransactionTokenBalance>>,
+) -> Vec<TokenBalanceChange> {
+    let mut changes = BTreeMap::new();
+    for (balances, is_pre) in [(pre, true), (post, false)] {
+        let OptionSerializer::Some(balances) = balances else {
+            continue;
+        };
+        for balance in balances {
+            let change =
+                changes
+                    .entry(balance.account_index)
+                    .or_insert_with(|| TokenBalanceChange {
+                        account: message
+                            .account_keys
+                            .get(balance.account_index as usize)
+                            .map(|key| key.pubkey.clone())
+                            .unwrap_or_default(),
+                        owner: Option::from(balance.owner.clone()),
+                        mint: balance.mint.clone(),
+                        decimals: balance.ui_token_amount.decimals,
+                        pre: None,
+                        post: None,
+                    });
+            let amount = Some(balance.ui_token_amount.ui_amount_string.clone());
+            if is_pre {
+                change.pre = amount;
+            } else {
+                change.post = amount;
+            }
+        }
+    }
+    changes.into_values().collect()
+}
+
+impl TxArgs {
+    pub async fn tx_handler(&self, settings: &Settings) -> Result<()> {
+        let rpc_client = get_rpc_client(settings)?;
+        match &self.command {
+            TxCommands::Show(args) => args.show(&rpc_client, settings).await,
+        }
+    }
+}
+
+impl TxShowArgs {
+    async fn show(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        let progress_bar = new_progress_bar(settings.output);
+        progress_bar.set_message("Fetching transaction...");
+        let transaction = fetch_transaction(rpc_client, &self.signature).await;
+        progress_bar.finish_and_clear();
+        let transaction = transaction?;
+        let parsed = ParsedTransaction::new(&transaction)?;
+        let (message, meta) = (parsed.message, parsed.meta);
+
+        let mut instructions = message
+            .instructions
+            .iter()
+            .map(|instruction| decode_instruction(instruction, message))
+            .collect::<Vec<_>>();
+        if let OptionSerializer::Some(inner_instructions) = &meta.inner_instructions {
+            for inner in inner_instructions {
+                if let Some(instruction) = instructions.get_mut(inner.index as usize) {
+                    instruction.inner_instructions.extend(
+                        inner
+                            .instructions
+                            .iter()
+                            .map(|inner| decode_instruction(inner, message)),
+                    );
+                }
+            }
+        }
+
+        let balances = message
+            .account_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| AccountBalance {
+                address: key.pubkey.clone(),
+                signer: key.signer,
+                writable: key.writable,
+                pre: meta.pre_balances.get(i).copied().unwrap_or_default(),
+                post: meta.post_balances.get(i).copied().unwrap_or_default(),
+            })
+            .collect();
+
+        let output = TransactionDetails {
+            signature: self.signature.to_string(),
+            slot: transaction.slot,
+            block_time: transaction.block_time,
+            time: transaction.block_time.map(format_block_time),
+            success: meta.err.is_none(),
+            error: meta.err.as_ref().map(|err| parsed.explain_error(err)),
+            raw_error: meta.err.clone(),
+            fee: meta.fee,
+            fee_payer: message
+                .account_keys
+                .first()
+                .map(|key| key.pubkey.clone())
+                .unwrap_or_default(),
+            signers: message
+                .account_keys
+                .iter()
+                .filter(|key| key.signer)
+                .map(|key| key.pubkey.clone())
+                .collect(),
+            recent_blockhash: message.recent_blockhash.clone(),
+            compute_units_consumed: meta.compute_units_consumed.clone().into(),
+            instructions,
+            balances,
+            token_balances: token_balance_changes(
+                message,
+                &meta.pre_token_balances,
+                &meta.post_token_balances,
+            ),
+            logs: Option::from(meta.log_messages.clone()).unwrap_or_default(),
+        };
+        print_output(&output, settings.output)?;
+        Ok(())
+    }
+}
+
+/// `post - pre` in lamports, saturating instead of wrapping when the change
+/// does not fit in an `i64`
+fn signed_delta(pre: u64, post: u64) -> i64 {
+    let delta = i128::from(post) - i128::from(pre);
+    delta.clamp(i64::MIN.into(), i64::MAX.into()) as i64
+}
+
+/// Formats a signed lamport amount as SOL with an explicit sign
+pub fn format_delta(lamports: i64) -> String {
+    let sol = format_sol(lamports.unsigned_abs());
+    match lamports.signum() {
+        1 => format!("+{} SOL", sol).green().to_string(),
+        -1 => format!("-{} SOL", sol).red().to_string(),
+        _ => format!("{} SOL", sol),
+    }
+}
+
+/// Formats a block time as a UTC date and time
+pub fn format_block_time(block_time: i64) -> String {
+    DateTime::from_timestamp(block_time, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| block_time.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_transaction_status::parse_accounts::ParsedAccount;
+    use solana_transaction_status::UiPartiallyDecodedInstruction;
+
+    fn message(accounts: &[&str], instructions: Vec<UiInstruction>) -> UiParsedMessage {
+        UiParsedMessage {
+            account_keys: accounts
+                .iter()
+                .enumerate()
+                .map(|(i, pubkey)| ParsedAccount {
+                    pubkey: pubkey.to_string(),
+                    writable: true,
+                    signer: i == 0,
+                    source: None,
+                })
+                .collect(),
+            recent_blockhash: Pubkey::new_unique().to_string(),
+            instructions,
+            address_table_lookups: None,
+        }
+    }
+
+    fn meta(pre_balances: Vec<u64>, post_balances: Vec<u64>) -> UiTransactionStatusMeta {
+        UiTransactionStatusMeta {
+            err: None,
+            status: Result::Ok(()),
+            fee: 5000,
+            pre_balances,
+            post_balances,
+            inner_instructions: OptionSerializer::None,
+            log_messages: OptionSerializer::None,
+            pre_token_balances: OptionSerializer::None,
+            post_token_balances: OptionSerializer::None,
+            rewards: OptionSerializer::None,
+            loaded_addresses: OptionSerializer::Skip,
+            return_data: OptionSerializer::Skip,
+            compute_units_consumed: OptionSerializer::Skip,
+        }
+    }
+
+    fn partially_decoded(program_id: &Pubkey, data: &[u8]) -> UiInstruction {
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(
+            UiPartiallyDecodedInstruction {
+                program_id: program_id.to_string(),
+                accounts: Vec::new(),
+                data: bs58::encode(data).into_string(),
+                stack_height: None,
+            },
+        ))
+    }
+
+    fn parsed(program: &str, program_id: &Pubkey) -> UiInstruction {
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(ParsedInstruction {
+            program: program.to_string(),
+            program_id: program_id.to_string(),
+            parsed: serde_json::json!({ "type": "transfer", "info": {} }),
+            stack_height: None,
+        }))
+    }
+
+    #[test]
+    fn lamports_delta_does_not_overflow() {
+        let accounts = ["payer", "recipient", "whale"];
+        let message = message(&accounts, Vec::new());
+        let meta = meta(vec![1_000_000, 0, u64::MAX], vec![895_000, 100_000, 0]);
+        let transaction = ParsedTransaction {
+            message: &message,
+            meta: &meta,
+        };
+        assert_eq!(transaction.lamports_delta("payer"), -105_000);
+        assert_eq!(transaction.lamports_delta("recipient"), 100_000);
+        assert_eq!(transaction.lamports_delta("whale"), i64::MIN);
+        assert_eq!(transaction.lamports_delta("stranger"), 0);
+        assert_eq!(signed_delta(0, u64::MAX), i64::MAX);
+    }
+
+    #[test]
+    fn explain_error_names_the_program_and_its_error() {
+        let payer = Pubkey::new_unique().to_string();
+        let recipient = Pubkey::new_unique().to_string();
+        let message = message(
+            &[&payer, &recipient],
+            vec![
+                parsed("system", &system_program::id()),
+                parsed("spl-token", &spl_token::id()),
+                partially_decoded(&Pubkey::new_unique(), &[1, 2, 3]),
+            ],
+        );
+        let meta = meta(vec![0, 0], vec![0, 0]);
+        let transaction = ParsedTransaction {
+            message: &message,
+            meta: &meta,
+        };
+        let instruction_error = |index, error| {
+            transaction.explain_error(&TransactionError::InstructionError(index, error))
+        };
+
+        assert_eq!(
+            instruction_error(0, InstructionError::Custom(1)),
+            format!(
+                "Instruction #1 (system) failed: {} (custom error 1)",
+                SystemError::ResultWithNegativeLamports
+            )
+        );
+        assert_eq!(
+            instruction_error(1, InstructionError::Custom(1)),
+            format!(
+                "Instruction #2 (spl-token) failed: {} (custom error 1)",
+                spl_token::error::TokenError::InsufficientFunds
+            )
+        );
+        assert!(instruction_error(2, InstructionError::Custom(42))
+            .ends_with("failed: custom program error 0x2a"));
+        assert_eq!(
+            instruction_error(0, InstructionError::MissingRequiredSignature),
+            format!(
+                "Instruction #1 (system) failed: {}",
+                InstructionError::MissingRequiredSignature
+            )
+        );
+        assert_eq!(
+            transaction
+                .explain_error(&TransactionError::InsufficientFundsForRent { account_index: 1 }),
+            format!("{} would be left below the rent-exempt minimum", recipient)
+        );
+        assert_eq!(
+            transaction.explain_error(&TransactionError::InsufficientFundsForFee),
+            format!("The fee payer {} can not pay the fee", payer)
+        );
+    }
+
+    #[test]
+    fn decodes_compute_budget_instructions() {
+        let decode = |instruction: solana_sdk::instruction::Instruction| {
+            decode_compute_budget(&bs58::encode(instruction.data).into_string())
+        };
+        assert_eq!(
+            decode(ComputeBudgetInstruction::set_compute_unit_limit(200_000)),
+            Some((
+                "setComputeUnitLimit".to_string(),
+                serde_json::json!({ "units": 200_000 })
+            ))
+        );
+        assert_eq!(
+            decode(ComputeBudgetInstruction::set_compute_unit_price(1_000)),
+            Some((
+                "setComputeUnitPrice".to_string(),
+                serde_json::json!({ "microLamports": 1_000 })
+            ))
+        );
+        assert_eq!(decode_compute_budget("not base58!"), None);
+        assert_eq!(decode_compute_budget(""), None);
+
+        let message = message(
+            &["payer"],
+            vec![partially_decoded(
+                &compute_budget::id(),
+                &ComputeBudgetInstruction::set_compute_unit_limit(300).data,
+            )],
+        );
+        let decoded = decode_instruction(&message.instructions[0], &message);
+        assert_eq!(decoded.program, "compute-budget");
+        assert_eq!(decoded.kind.as_deref(), Some("setComputeUnitLimit"));
+    }
+}
@@ -0,0 +1,403 @@
+use crate::amount::{format_sol, Amount};
+use crate::args::get_rpc_client;
+use crate::config::Settings;
+use crate::output::{print_output, print_outputs, CommandOutput};
+use crate::policy::MainnetGuard;
+use crate::priority_fee::PriorityFeeArgs;
+use crate::sender::{new_progress_bar, Stage};
+use crate::signer::SignerSource;
+use crate::token::{
+    get_token_accounts, parse_units, price_token_transaction, send_priced_token_transaction,
+    write_confirmation,
+};
+use anyhow::Result;
+use clap::Args;
+use colored::*;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::signer::Signer;
+use solana_sdk::system_instruction;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use std::fmt;
+
+/// How many wSOL accounts `unwrap --all` closes per transaction
+const MAX_CLOSES_PER_TRANSACTION: usize = 20;
+
+#[derive(Args, Clone, Debug)]
+pub struct WrapArgs {
+    /// Amount of SOL to wrap e.g 1.5, 1.5SOL or 2500lamports
+    #[arg(short = 'v', long, allow_negative_numbers = true)]
+    pub value: Amount,
+    /// The keypair file or signer URI of the wallet wrapping SOL, defaults to
+    /// the keypair in your config profile
+    #[arg(short, long)]
+    pub keypair: Option<SignerSource>,
+    /// Send on mainnet without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+    #[command(flatten)]
+    pub priority_fee: PriorityFeeArgs,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct UnwrapArgs {
+    /// The keypair file or signer URI of the wallet unwrapping SOL, defaults
+    /// to the keypair in your config profile
+    #[arg(short, long)]
+    pub keypair: Option<SignerSource>,
+    /// Close every wSOL account owned by the wallet, not only its associated
+    /// token account
+    #[arg(long)]
+    pub all: bool,
+    /// Send on mainnet without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+    #[command(flatten)]
+    pub priority_fee: PriorityFeeArgs,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SolWrapped {
+    pub owner: String,
+    /// The wallet's wSOL associated token account
+    pub account: String,
+    pub lamports: u64,
+    /// Whether the wSOL account was created to wrap into it
+    pub created_account: bool,
+    /// Total fee in lamports, including `priority_fee`
+    pub fee: u64,
+    pub priority_fee: u64,
+    pub signature: String,
+    pub slot: u64,
+    pub status: Stage,
+    /// How many times the transaction was re-signed after its blockhash expired
+    pub resigned: usize,
+}
+
+impl fmt::Display for SolWrapped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.created_account {
+            writeln!(f, "Created the wSOL account {}", self.account)?;
+        }
+        writeln!(
+            f,
+            "Wrapped {} SOL into {}",
+            format_sol(self.lamports).green().bold(),
+            self.account.blue()
+        )?;
+        write_confirmation(
+            f,
+            self.fee,
+            self.priority_fee,
+            self.status,
+            self.slot,
+            self.resigned,
+        )?;
+        write!(f, "Signature: {}", self.signature.yellow().bold())
+    }
+}
+
+impl CommandOutput for SolWrapped {}
+
+/// The wSOL accounts closed by one unwrap transaction
+#[derive(Serialize, Debug)]
+pub struct SolUnwrapped {
+    pub owner: String,
+    pub accounts: Vec<String>,
+    /// Wrapped lamports returned to the wallet, excluding rent
+    pub lamports: u64,
+    /// Rent lamports returned to the wallet by closing the accounts
+    pub rent: u64,
+    /// Total fee in lamports, including `priority_fee`
+    pub fee: u64,
+    pub priority_fee: u64,
+    pub signature: String,
+    pub slot: u64,
+    pub status: Stage,
+    /// How many times the transaction was re-signed after its blockhash expired
+    pub resigned: usize,
+}
+
+impl fmt::Display for SolUnwrapped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Unwrapped {} SOL and reclaimed {} SOL of rent by closing:",
+            format_sol(self.lamports).green().bold(),
+            format_sol(self.rent)
+        )?;
+        for account in &self.accounts {
+            writeln!(f, "  {}", account.blue())?;
+        }
+        write_confirmation(
+            f,
+            self.fee,
+            self.priority_fee,
+            self.status,
+            self.slot,
+            self.resigned,
+        )?;
+        write!(f, "Signature: {}", self.signature.yellow().bold())
+    }
+}
+
+/// A `SolUnwrapped` as a CSV record, with the accounts space separated
+#[derive(Serialize)]
+struct SolUnwrappedRow<'a> {
+    owner: &'a str,
+    accounts: String,
+    lamports: u64,
+    rent: u64,
+    fee: u64,
+    priority_fee: u64,
+    signature: &'a str,
+    slot: u64,
+    status: Stage,
+    resigned: usize,
+}
+
+impl CommandOutput for SolUnwrapped {
+    fn write_csv(&self, writer: &mut csv::Writer<Vec<u8>>) -> Result<()> {
+        writer.serialize(SolUnwrappedRow {
+            owner: &self.owner,
+            accounts: self.accounts.join(" "),
+            lamports: self.lamports,
+            rent: self.rent,
+            fee: self.fee,
+            priority_fee: self.priority_fee,
+            signature: &self.signature,
+            slot: self.slot,
+            status: self.status,
+            resigned: self.resigned,
+        })?;
+        Ok(())
+    }
+}
+
+impl WrapArgs {
+    pub async fn wrap_handler(&self, settings: &Settings) -> Result<()> {
+        let rpc_client = get_rpc_client(settings)?;
+        self.wrap_sol(&rpc_client, settings).await
+    }
+
+    async fn wrap_sol(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        let progress_bar = new_progress_bar(settings.output);
+
+        let owner = settings.keypair_or_default(&self.keypair)?.keypair()?;
+        let lamports = self.value.lamports("wrap")?;
+        if lamports == 0 {
+            anyhow::bail!("Nothing to wrap, the amount is 0");
+        }
+        let native_mint = spl_token::native_mint::id();
+        let account = get_associated_token_address_with_program_id(
+            &owner.pubkey(),
+            &native_mint,
+            &spl_token::id(),
+        );
+
+        progress_bar.set_message("Looking up wSOL account...");
+        let created_account = rpc_client
+            .get_account_with_commitment(&account, rpc_client.commitment())
+            .await?
+            .value
+            .is_none();
+
+        progress_bar.set_message("Creating transaction...");
+        let mut instructions: Vec<Instruction> = Vec::new();
+        if created_account {
+            instructions.push(create_associated_token_account_idempotent(
+                &owner.pubkey(),
+                &owner.pubkey(),
+                &native_mint,
+                &spl_token::id(),
+            ));
+        }
+        // The lamports only count as tokens once the account is synced
+        instructions.push(system_instruction::transfer(
+            &owner.pubkey(),
+            &account,
+            lamports,
+        ));
+        instructions.push(spl_token::instruction::sync_native(
+            &spl_token::id(),
+            &account,
+        )?);
+        let priced = price_token_transaction(
+            rpc_client,
+            &self.priority_fee,
+            &instructions,
+            &owner.pubkey(),
+        )
+        .await?;
+
+        // The fee and the rent of a new wSOL account come out of the same balance
+        let rent = if created_account {
+            rpc_client
+                .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+                .await?
+        } else {
+            0
+        };
+        let needed = lamports.saturating_add(priced.fee).saturating_add(rent);
+        let balance = rpc_client.get_balance(&owner.pubkey()).await?;
+        if needed > balance {
+            anyhow::bail!(
+                "Insufficient balance, wrapping {} SOL needs {} SOL with the fee and the rent of the wSOL account, {} SOL available",
+                format_sol(lamports),
+                format_sol(needed),
+                format_sol(balance)
+            );
+        }
+
+        let guard = MainnetGuard::new(rpc_client, settings).await?;
+        let summary = [
+            format!("Wallet: {}", owner.pubkey()),
+            format!("Wrap: {} SOL into {}", format_sol(lamports), account),
+            format!("Fee: {} SOL", format_sol(priced.fee)),
+        ];
+        progress_bar.suspend(|| guard.confirm(&summary, self.yes))?;
+
+        let sent =
+            send_priced_token_transaction(rpc_client, priced, &owner, &[], &progress_bar).await?;
+        let output = SolWrapped {
+            owner: owner.pubkey().to_string(),
+            account: account.to_string(),
+            lamports,
+            created_account,
+            fee: sent.fee,
+            priority_fee: sent.priority_fee,
+            signature: sent.confirmation.signature.to_string(),
+            slot: sent.confirmation.slot,
+            status: sent.confirmation.status,
+            resigned: sent.confirmation.resigned,
+        };
+        print_output(&output, settings.output)?;
+        Ok(())
+    }
+}
+
+impl UnwrapArgs {
+    pub async fn unwrap_handler(&self, settings: &Settings) -> Result<()> {
+        let rpc_client = get_rpc_client(settings)?;
+        self.unwrap_sol(&rpc_client, settings).await
+    }
+
+    async fn unwrap_sol(&self, rpc_client: &RpcClient, settings: &Settings) -> Result<()> {
+        let owner = settings.keypair_or_default(&self.keypair)?.keypair()?;
+        let native_mint = spl_token::native_mint::id();
+        let associated_account = get_associated_token_address_with_program_id(
+            &owner.pubkey(),
+            &native_mint,
+            &spl_token::id(),
+        );
+
+        let (mut accounts, locked): (Vec<_>, Vec<_>) =
+            get_token_accounts(rpc_client, &owner.pubkey(), Some(&native_mint))
+                .await?
+                .into_iter()
+                .filter(|account| self.all || account.address == associated_account)
+                // Accounts with another close authority can't be closed by the owner
+                .partition(|account| {
+                    account
+                        .info
+                        .close_authority
+                        .as_ref()
+                        .is_none_or(|authority| *authority == owner.pubkey().to_string())
+                });
+        if accounts.is_empty() {
+            match (locked.as_slice(), self.all) {
+                ([], true) => {
+                    anyhow::bail!("{} has no wSOL account to unwrap", owner.pubkey())
+                }
+                ([], false) => anyhow::bail!(
+                    "{} has no associated wSOL account, pass `--all` to unwrap its other wSOL accounts",
+                    owner.pubkey()
+                ),
+                ([account], false) => anyhow::bail!(
+                    "The associated wSOL account {} can only be closed by its close authority {}",
+                    account.address,
+                    account.info.close_authority.as_deref().unwrap_or_default()
+                ),
+                (locked, _) => anyhow::bail!(
+                    "The {} wSOL account(s) of {} can only be closed by another close authority",
+                    locked.len(),
+                    owner.pubkey()
+                ),
+            }
+        }
+        // Closing the associated account first keeps the common case in the
+        // first transaction
+        accounts.sort_by_key(|account| account.address != associated_account);
+
+        // Every transaction is priced first, so the whole unwrap is
+        // confirmed before anything is sent
+        let mut transactions = Vec::new();
+        for chunk in accounts.chunks(MAX_CLOSES_PER_TRANSACTION) {
+            let mut instructions = Vec::new();
+            let mut lamports = 0;
+            let mut rent = 0;
+            for account in chunk {
+                instructions.push(spl_token_2022::instruction::close_account(
+                    &account.program_id,
+                    &account.address,
+                    &owner.pubkey(),
+                    &owner.pubkey(),
+                    &[],
+                )?);
+                lamports += account.amount()?;
+                if let Some(reserve) = &account.info.rent_exempt_reserve {
+                    rent += parse_units(&reserve.amount)?;
+                }
+            }
+            let priced = price_token_transaction(
+                rpc_client,
+                &self.priority_fee,
+                &instructions,
+                &owner.pubkey(),
+            )
+            .await?;
+            transactions.push((chunk, lamports, rent, priced));
+        }
+
+        let guard = MainnetGuard::new(rpc_client, settings).await?;
+        let summary = [
+            format!("Wallet: {}", owner.pubkey()),
+            format!(
+                "Unwrap: {} SOL from {} wSOL account(s)",
+                format_sol(transactions.iter().map(|(_, l, _, _)| l).sum()),
+                accounts.len()
+            ),
+            format!(
+                "Fee: {} SOL",
+                format_sol(transactions.iter().map(|(_, _, _, p)| p.fee).sum())
+            ),
+        ];
+        guard.confirm(&summary, self.yes)?;
+
+        let mut outputs = Vec::new();
+        for (chunk, lamports, rent, priced) in transactions {
+            let progress_bar = new_progress_bar(settings.output);
+            progress_bar.set_message("Sending transaction...");
+            let sent =
+                send_priced_token_transaction(rpc_client, priced, &owner, &[], &progress_bar)
+                    .await?;
+            outputs.push(SolUnwrapped {
+                owner: owner.pubkey().to_string(),
+                accounts: chunk.iter().map(|a| a.address.to_string()).collect(),
+                lamports,
+                rent,
+                fee: sent.fee,
+                priority_fee: sent.priority_fee,
+                signature: sent.confirmation.signature.to_string(),
+                slot: sent.confirmation.slot,
+                status: sent.confirmation.status,
+                resigned: sent.confirmation.resigned,
+            });
+        }
+        print_outputs(outputs, settings.output)?;
+        Ok(())
+    }
+}